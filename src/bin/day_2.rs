@@ -1,9 +1,12 @@
-use advent_of_code_2022::utils::init_logger;
+use advent_of_code_2022::utils::{flag_value, has_flag, init_logger, Xorshift};
 use anyhow::Result;
 use rayon::{
     prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    slice::ParallelSlice,
     str::ParallelString,
 };
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 
 const INPUT: &str = include_str!("../../inputs/day2.txt");
 
@@ -26,14 +29,51 @@ enum GameOutput {
     Part2((Attack, GameOutcome)),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 enum Attack {
     Rock,
     Paper,
     Scissors,
 }
 
-#[derive(Debug, Clone)]
+impl Attack {
+    const ALL: [Self; 3] = [Self::Rock, Self::Paper, Self::Scissors];
+
+    const fn index(&self) -> usize {
+        match self {
+            Self::Rock => 0,
+            Self::Paper => 1,
+            Self::Scissors => 2,
+        }
+    }
+
+    // the attack that loses against this one
+    const fn beats(&self) -> Self {
+        match self {
+            Self::Rock => Self::Scissors,
+            Self::Paper => Self::Rock,
+            Self::Scissors => Self::Paper,
+        }
+    }
+
+    // the attack that wins against this one
+    const fn beaten_by(&self) -> Self {
+        Self::ALL[(self.index() + 1) % 3]
+    }
+
+    // how a round ends for me when i throw this against `theirs`
+    fn against(&self, theirs: &Self) -> GameOutcome {
+        if self == theirs {
+            GameOutcome::Draw
+        } else if &self.beats() == theirs {
+            GameOutcome::Win
+        } else {
+            GameOutcome::Lose
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 enum GameOutcome {
     Win,
     Lose,
@@ -48,11 +88,6 @@ trait Game {
     fn game_tally(&self) -> u32;
 }
 
-trait OutPutKey {
-    fn to_attack(&self) -> Result<Attack>;
-    fn to_game_result(&self) -> Result<GameOutcome>;
-}
-
 impl Round for GameOutput {
     fn round_score(&self) -> u32 {
         let mut score = 0;
@@ -121,99 +156,1308 @@ impl Game for Vec<GameOutput> {
     }
 }
 
-impl OutPutKey for Option<&String> {
-    // map a given input to a logical attack enum
-    fn to_attack(&self) -> Result<Attack> {
-        match *self {
-            Some(play) => match play.trim().to_uppercase().as_ref() {
-                "A" | "X" => Ok(Attack::Rock),
-                "B" | "Y" => Ok(Attack::Paper),
-                "C" | "Z" => Ok(Attack::Scissors),
-                _ => anyhow::bail!("{play} not supported"),
-            },
-            None => anyhow::bail!("play option not found"),
+/// how the second column of the strategy guide should be read
+#[derive(Debug, Clone)]
+enum ColumnKey {
+    // part 1 reading: the column is the attack i throw
+    Attack(BTreeMap<String, Attack>),
+    // part 2 reading: the column is how the round needs to end
+    Outcome(BTreeMap<String, GameOutcome>),
+}
+
+/// meaning of every letter in the strategy guide
+///
+/// written as two groups of `LETTER=meaning` pairs, opponent column first,
+/// eg `A=rock,B=paper,C=scissors;X=lose,Y=draw,Z=win`
+#[derive(Debug, Clone)]
+struct StrategyKey {
+    opponent: BTreeMap<String, Attack>,
+    column: ColumnKey,
+}
+
+// every ordering of three values, used to shuffle the second column
+const PERMUTATIONS: [[usize; 3]; 6] = [
+    [0, 1, 2],
+    [0, 2, 1],
+    [1, 0, 2],
+    [1, 2, 0],
+    [2, 0, 1],
+    [2, 1, 0],
+];
+
+impl StrategyKey {
+    const ATTACKS: [Attack; 3] = Attack::ALL;
+    const OUTCOMES: [GameOutcome; 3] = [GameOutcome::Lose, GameOutcome::Draw, GameOutcome::Win];
+
+    fn part1() -> Self {
+        Self {
+            opponent: Self::letters(["A", "B", "C"], Self::ATTACKS),
+            column: ColumnKey::Attack(Self::letters(["X", "Y", "Z"], Self::ATTACKS)),
         }
     }
 
-    // for pt 2, we discover our input key was actually game result
-    fn to_game_result(&self) -> Result<GameOutcome> {
-        match *self {
-            Some(play) => match play.trim().to_uppercase().as_ref() {
-                "X" => Ok(GameOutcome::Lose),
-                "Y" => Ok(GameOutcome::Draw),
-                "Z" => Ok(GameOutcome::Win),
-                _ => anyhow::bail!("{play} not supported"),
-            },
+    fn part2() -> Self {
+        Self {
+            opponent: Self::letters(["A", "B", "C"], Self::ATTACKS),
+            column: ColumnKey::Outcome(Self::letters(["X", "Y", "Z"], Self::OUTCOMES)),
+        }
+    }
+
+    fn letters<T>(letters: [&str; 3], values: [T; 3]) -> BTreeMap<String, T> {
+        letters
+            .into_iter()
+            .map(std::string::ToString::to_string)
+            .zip(values)
+            .collect()
+    }
+
+    fn parse(spec: &str) -> Result<Self> {
+        let groups = spec
+            .split(['\n', ';'])
+            .map(str::trim)
+            .filter(|group| !group.is_empty() && !group.starts_with('#'))
+            .map(|group| {
+                group
+                    .split([',', ' ', '\t'])
+                    .filter(|pair| !pair.is_empty())
+                    .map(|pair| match pair.split_once('=') {
+                        Some((letter, meaning)) => {
+                            Ok((letter.trim().to_uppercase(), meaning.trim().to_lowercase()))
+                        }
+                        None => anyhow::bail!("expected LETTER=meaning, found {pair}"),
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (opponent, column) = match groups.as_slice() {
+            [opponent, column] => (opponent, column),
+            _ => anyhow::bail!("expected 2 key groups, found {}", groups.len()),
+        };
+        let opponent = Self::column_of(opponent, Self::to_attack)?;
+        let column = if column
+            .iter()
+            .all(|(_, meaning)| Self::to_attack(meaning).is_ok())
+        {
+            ColumnKey::Attack(Self::column_of(column, Self::to_attack)?)
+        } else {
+            ColumnKey::Outcome(Self::column_of(column, Self::to_game_result)?)
+        };
+        Ok(Self { opponent, column })
+    }
+
+    fn column_of<T: PartialEq + Clone>(
+        pairs: &[(String, String)],
+        to_value: fn(&str) -> Result<T>,
+    ) -> Result<BTreeMap<String, T>> {
+        let mut column = BTreeMap::new();
+        let mut values: Vec<T> = vec![];
+        for (letter, meaning) in pairs {
+            let value = to_value(meaning)?;
+            if values.contains(&value) {
+                anyhow::bail!("{meaning} is mapped more than once");
+            }
+            values.push(value.clone());
+            if column.insert(letter.clone(), value).is_some() {
+                anyhow::bail!("{letter} is mapped more than once");
+            }
+        }
+        if column.len() != 3 {
+            anyhow::bail!("expected 3 letters per column, found {}", column.len());
+        }
+        Ok(column)
+    }
+
+    fn to_attack(meaning: &str) -> Result<Attack> {
+        match meaning {
+            "rock" => Ok(Attack::Rock),
+            "paper" => Ok(Attack::Paper),
+            "scissors" => Ok(Attack::Scissors),
+            _ => anyhow::bail!("{meaning} is not an attack"),
+        }
+    }
+
+    fn to_game_result(meaning: &str) -> Result<GameOutcome> {
+        match meaning {
+            "lose" | "loss" => Ok(GameOutcome::Lose),
+            "draw" => Ok(GameOutcome::Draw),
+            "win" => Ok(GameOutcome::Win),
+            _ => anyhow::bail!("{meaning} is not a game outcome"),
+        }
+    }
+
+    // map a given input line to a round of the game
+    fn decode(&self, line: &str) -> Result<GameOutput> {
+        let throws = line.split_whitespace().collect::<Vec<_>>();
+        let lookup = |play: Option<&&str>| match play {
+            Some(play) => Ok(play.trim().to_uppercase()),
             None => anyhow::bail!("play option not found"),
+        };
+        let (theirs, mine) = (lookup(throws.first())?, lookup(throws.last())?);
+        let their_attack = match self.opponent.get(&theirs) {
+            Some(attack) => *attack,
+            None => anyhow::bail!("{theirs} not supported"),
+        };
+        match &self.column {
+            ColumnKey::Attack(key) => match key.get(&mine) {
+                Some(attack) => Ok(GameOutput::Part1((their_attack, *attack))),
+                None => anyhow::bail!("{mine} not supported"),
+            },
+            ColumnKey::Outcome(key) => match key.get(&mine) {
+                Some(outcome) => Ok(GameOutput::Part2((their_attack, outcome.clone()))),
+                None => anyhow::bail!("{mine} not supported"),
+            },
+        }
+    }
+
+    // same key, with the meanings of the second column shuffled every possible way
+    fn permutations(&self) -> Vec<Self> {
+        fn shuffle<T: Clone>(key: &BTreeMap<String, T>, order: [usize; 3]) -> BTreeMap<String, T> {
+            let values = key.values().cloned().collect::<Vec<_>>();
+            key.keys()
+                .cloned()
+                .zip(order.map(|i| values[i].clone()))
+                .collect()
+        }
+        PERMUTATIONS
+            .iter()
+            .map(|order| Self {
+                opponent: self.opponent.clone(),
+                column: match &self.column {
+                    ColumnKey::Attack(key) => ColumnKey::Attack(shuffle(key, *order)),
+                    ColumnKey::Outcome(key) => ColumnKey::Outcome(shuffle(key, *order)),
+                },
+            })
+            .collect()
+    }
+
+    // just the opponent's attacks, the column the solver plays against
+    fn opponent_column(&self, lines: &[&str]) -> Result<Vec<Attack>> {
+        lines
+            .par_iter()
+            .map(|line| {
+                let theirs = line.split_whitespace().next().unwrap_or_default();
+                match self.opponent.get(&theirs.to_uppercase()) {
+                    Some(attack) => Ok(*attack),
+                    None => anyhow::bail!("{theirs} not supported"),
+                }
+            })
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        fn pairs<T: std::fmt::Debug>(key: &BTreeMap<String, T>) -> String {
+            key.iter()
+                .map(|(letter, value)| format!("{letter}={value:?}"))
+                .collect::<Vec<_>>()
+                .join(",")
         }
+        let column = match &self.column {
+            ColumnKey::Attack(key) => pairs(key),
+            ColumnKey::Outcome(key) => pairs(key),
+        };
+        format!("{};{column}", pairs(&self.opponent)).to_lowercase()
     }
 }
 
-fn part1(lines: &[&str]) -> Vec<GameOutput> {
-    let start = std::time::Instant::now();
-    let rounds = lines
+fn play_guide(lines: &[&str], key: &StrategyKey) -> Result<Vec<GameOutput>> {
+    lines
         .par_iter()
-        .map(|line| -> GameOutput {
-            let throws = line
-                .split_whitespace()
+        .map(|line| key.decode(line))
+        .collect::<Result<Vec<_>>>()
+}
+
+// partial guides kept across every round before the in order solver gives up
+const MAX_GUIDE_STATES: usize = 1 << 22;
+// rounds of adjusting the prices that bound the in order solver
+const PRICE_STEPS: u32 = 1000;
+// partial guides the in order solver follows each round for its first guess
+const BEAM_WIDTH: usize = 1 << 11;
+// the most partial guides a round it follows before settling for the search alone
+const MAX_BEAM_WIDTH: usize = 1 << 13;
+
+/// limits the solver has to respect while building a strategy guide
+#[derive(Debug, Clone, Default)]
+struct Constraints {
+    // each attack may be thrown at most this many times
+    max_per_move: Option<usize>,
+    // exactly this many rounds must be lost
+    losses: Option<usize>,
+    // the same attack can't be thrown two rounds in a row
+    no_repeat: bool,
+}
+
+// score of throwing `mine` against `theirs`, straight from the part 1 rules
+fn throw_score(theirs: &Attack, mine: &Attack) -> i64 {
+    i64::from(GameOutput::Part1((*theirs, *mine)).round_score())
+}
+
+// a throw's score less the price of the attack, and of losing if it does
+fn priced_score(theirs: &Attack, mine: &Attack, prices: &[f64; 4]) -> f64 {
+    let lost = if theirs.beats() == *mine {
+        prices[3]
+    } else {
+        0.0
+    };
+    throw_score(theirs, mine) as f64 - prices[mine.index()] - lost
+}
+
+/// tiny min cost flow over `source -> opponent attack -> my attack -> sink`
+///
+/// with no ordering constraint only the count of each (theirs, mine) pair matters,
+/// so the best guide is a 3x3 transportation plan
+struct TransportPlan {
+    // (to, capacity, cost), paired with its reverse edge at `index ^ 1`
+    edges: Vec<(usize, i64, i64)>,
+    graph: Vec<Vec<usize>>,
+}
+
+impl TransportPlan {
+    const SOURCE: usize = 0;
+    const SINK: usize = 7;
+
+    // opponent attacks are nodes 1..=3, mine are 4..=6
+    fn new(supply: [i64; 3], capacity: [i64; 3], allowed: [[bool; 3]; 3]) -> Self {
+        let mut plan = Self {
+            edges: vec![],
+            graph: vec![vec![]; 8],
+        };
+        for theirs in Attack::ALL {
+            let t = theirs.index();
+            plan.add_edge(Self::SOURCE, 1 + t, supply[t], 0);
+            for mine in Attack::ALL {
+                let m = mine.index();
+                if allowed[t][m] {
+                    plan.add_edge(1 + t, 4 + m, supply[t], -throw_score(&theirs, &mine));
+                }
+            }
+        }
+        for (m, capacity) in capacity.into_iter().enumerate() {
+            plan.add_edge(4 + m, Self::SINK, capacity, 0);
+        }
+        plan
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i64, cost: i64) {
+        self.graph[from].push(self.edges.len());
+        self.edges.push((to, capacity, cost));
+        self.graph[to].push(self.edges.len());
+        self.edges.push((from, 0, -cost));
+    }
+
+    // push `required` units at minimum cost, returns the best score and the plan
+    fn solve(mut self, required: i64) -> Option<(i64, [[i64; 3]; 3])> {
+        let mut flow = 0;
+        let mut cost = 0;
+        while flow < required {
+            // bellman-ford, the graph is far too small to bother with potentials
+            let mut distance = [i64::MAX; 8];
+            let mut via = [usize::MAX; 8];
+            distance[Self::SOURCE] = 0;
+            for _ in 0..8 {
+                for node in 0..8 {
+                    if distance[node] == i64::MAX {
+                        continue;
+                    }
+                    for &edge in &self.graph[node] {
+                        let (to, capacity, edge_cost) = self.edges[edge];
+                        if capacity > 0 && distance[node] + edge_cost < distance[to] {
+                            distance[to] = distance[node] + edge_cost;
+                            via[to] = edge;
+                        }
+                    }
+                }
+            }
+            if distance[Self::SINK] == i64::MAX {
+                return None;
+            }
+            let mut push = required - flow;
+            let mut node = Self::SINK;
+            while node != Self::SOURCE {
+                let edge = via[node];
+                push = push.min(self.edges[edge].1);
+                node = self.edges[edge ^ 1].0;
+            }
+            let mut node = Self::SINK;
+            while node != Self::SOURCE {
+                let edge = via[node];
+                self.edges[edge].1 -= push;
+                self.edges[edge ^ 1].1 += push;
+                node = self.edges[edge ^ 1].0;
+            }
+            flow += push;
+            cost += push * distance[Self::SINK];
+        }
+        let mut plan = [[0; 3]; 3];
+        for (t, row) in plan.iter_mut().enumerate() {
+            for &edge in &self.graph[1 + t] {
+                let (to, _, _) = self.edges[edge];
+                // forward edges carry flow as the capacity of their reverse edge
+                if (4..7).contains(&to) && edge % 2 == 0 {
+                    row[to - 4] += self.edges[edge ^ 1].1;
+                }
+            }
+        }
+        Some((-cost, plan))
+    }
+}
+
+trait GuideSolver {
+    fn solve_guide(&self, constraints: &Constraints) -> Result<Vec<Attack>>;
+}
+
+impl GuideSolver for [Attack] {
+    // best attack to throw every round, given the opponent's column
+    fn solve_guide(&self, constraints: &Constraints) -> Result<Vec<Attack>> {
+        if let Some(losses) = constraints.losses {
+            if losses > self.len() {
+                anyhow::bail!("can't lose {losses} of {} rounds", self.len());
+            }
+        }
+        if constraints.no_repeat {
+            return self.solve_in_order(constraints);
+        }
+        self.solve_by_count(constraints)
+    }
+}
+
+trait GuideSolverHelpers {
+    fn solve_by_count(&self, constraints: &Constraints) -> Result<Vec<Attack>>;
+    fn solve_in_order(&self, constraints: &Constraints) -> Result<Vec<Attack>>;
+    fn priced_ahead(&self, prices: &[f64; 4]) -> Vec<[f64; 4]>;
+    fn losses_ahead(&self) -> Vec<[(usize, usize); 4]>;
+    fn alternating_losses(&self) -> Vec<[[usize; 3]; 3]>;
+    fn priced_guide(&self, limits: &[Option<usize>; 4], prices: &[f64; 4]) -> (f64, [usize; 4]);
+    fn search_in_order(
+        &self,
+        caps: &[Option<usize>; 3],
+        losses: Option<usize>,
+        prices: &[f64; 4],
+        threshold: i64,
+        width: Option<usize>,
+    ) -> Result<Option<Vec<Attack>>>;
+}
+
+impl GuideSolverHelpers for [Attack] {
+    fn solve_by_count(&self, constraints: &Constraints) -> Result<Vec<Attack>> {
+        let rounds = i64::try_from(self.len())?;
+        let mut supply = [0; 3];
+        for attack in self {
+            supply[attack.index()] += 1;
+        }
+        let capacity = match constraints.max_per_move {
+            Some(max) => i64::try_from(max)?,
+            None => rounds,
+        };
+        let best = match constraints.losses {
+            // free to lose whenever the move caps force it
+            None => TransportPlan::new(supply, [capacity; 3], [[true; 3]; 3]).solve(rounds),
+            // fix how many of each opponent attack i lose to, plan the rest
+            Some(losses) => {
+                let losses = i64::try_from(losses)?;
+                let mut best: Option<(i64, [[i64; 3]; 3])> = None;
+                for rock in 0..=losses.min(supply[0]) {
+                    for paper in 0..=(losses - rock).min(supply[1]) {
+                        let lost = [rock, paper, losses - rock - paper];
+                        if lost[2] > supply[2] {
+                            continue;
+                        }
+                        let mut remaining = [capacity; 3];
+                        let mut allowed = [[true; 3]; 3];
+                        let mut base = 0;
+                        for theirs in Attack::ALL {
+                            let (t, m) = (theirs.index(), theirs.beats().index());
+                            remaining[m] -= lost[t];
+                            allowed[t][m] = false;
+                            base += lost[t] * throw_score(&theirs, &theirs.beats());
+                        }
+                        if remaining.iter().any(|cap| *cap < 0) {
+                            continue;
+                        }
+                        let plan = TransportPlan::new(
+                            [0, 1, 2].map(|t| supply[t] - lost[t]),
+                            remaining,
+                            allowed,
+                        )
+                        .solve(rounds - losses);
+                        if let Some((score, mut plan)) = plan {
+                            if best.as_ref().map_or(true, |(best, _)| base + score > *best) {
+                                for theirs in Attack::ALL {
+                                    plan[theirs.index()][theirs.beats().index()] =
+                                        lost[theirs.index()];
+                                }
+                                best = Some((base + score, plan));
+                            }
+                        }
+                    }
+                }
+                best
+            }
+        };
+        let (_, mut plan) = match best {
+            Some(best) => best,
+            None => anyhow::bail!("no strategy guide satisfies {constraints:?}"),
+        };
+        // hand out the planned throws in input order
+        Ok(self
+            .iter()
+            .map(|theirs| {
+                let row = &mut plan[theirs.index()];
+                let m = row.iter().position(|count| *count > 0).unwrap_or_default();
+                row[m] -= 1;
+                Attack::ALL[m]
+            })
+            .collect())
+    }
+
+    fn solve_in_order(&self, constraints: &Constraints) -> Result<Vec<Attack>> {
+        let cap = constraints.max_per_move.filter(|max| *max < self.len());
+        if cap.map_or(false, |cap| cap * 3 < self.len()) {
+            anyhow::bail!("no strategy guide satisfies {constraints:?}");
+        }
+        // price each attack and each loss so the guide that ignores the caps and
+        // the loss count bounds the ones that keep them, the lowest bound found wins
+        let limits = [cap, cap, cap, constraints.losses];
+        let mut best = (f64::INFINITY, [0.0; 4]);
+        let mut prices = [0.0; 4];
+        for step in 0..PRICE_STEPS {
+            let (bound, used) = self.priced_guide(&limits, &prices);
+            if bound < best.0 {
+                best = (bound, prices);
+            }
+            let over = [0, 1, 2, 3].map(|index| match limits[index] {
+                Some(limit) => used[index] as f64 - limit as f64,
+                None => 0.0,
+            });
+            let norm = over.iter().map(|over| over * over).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                break;
+            }
+            let size = 2.0 * 0.99_f64.powi(step as i32);
+            for index in 0..4 {
+                prices[index] += size * over[index] / norm;
+            }
+            // only the loss count is exact, the caps can't earn a negative price
+            for price in &mut prices[..3] {
+                *price = price.max(0.0);
+            }
+        }
+        let (bound, prices) = best;
+        let bound = (bound + 1e-6).floor() as i64;
+        let score = |guide: &[Attack]| {
+            self.iter()
+                .zip(guide)
+                .map(|(theirs, mine)| throw_score(theirs, mine))
+                .sum::<i64>()
+        };
+        let throws = |guide: &[Attack], attack: usize| {
+            guide.iter().filter(|mine| mine.index() == attack).count()
+        };
+        // a guide that keeps every constraint, following only the most promising
+        // partial guides each round, and more of them whenever none make it to
+        // the end, is the answer if it reaches the bound
+        let caps = [cap; 3];
+        let mut width = BEAM_WIDTH;
+        let beam = loop {
+            match self.search_in_order(&caps, constraints.losses, &prices, 0, Some(width))? {
+                None if width < MAX_BEAM_WIDTH => width *= 2,
+                beam => break beam,
+            }
+        };
+        let found = beam.as_deref().map(score);
+        if let (Some(guide), true) = (&beam, found == Some(bound)) {
+            return Ok(guide.clone());
+        }
+        // otherwise search everything that could beat it, only counting the
+        // attacks the prices say are short, and any the answer goes over
+        let mut caps = [0, 1, 2].map(|index| cap.filter(|_| prices[index] > 0.0));
+        let mut slack = 0;
+        loop {
+            let threshold = found.map_or(bound - slack, |found| found + 1);
+            match self.search_in_order(&caps, constraints.losses, &prices, threshold, None) {
+                Ok(Some(guide)) => {
+                    let over = (0..3).find(|index| {
+                        caps[*index].is_none()
+                            && cap.map_or(false, |cap| throws(&guide, *index) > cap)
+                    });
+                    match over {
+                        Some(index) => caps[index] = cap,
+                        None => return Ok(guide),
+                    }
+                }
+                // nothing beats the first guess, or nothing within `slack` of the bound
+                Ok(None) => match &beam {
+                    Some(guide) => return Ok(guide.clone()),
+                    None if threshold <= 0 => {
+                        anyhow::bail!("no strategy guide satisfies {constraints:?}")
+                    }
+                    None => slack = (slack * 2).max(1),
+                },
+                Err(err) => match (&beam, found) {
+                    (Some(guide), Some(found)) => {
+                        tracing::warn!("{err}, keeping a guide at most {} short", bound - found);
+                        return Ok(guide.clone());
+                    }
+                    _ => return Err(err),
+                },
+            }
+        }
+    }
+
+    // the most the rounds from each one on can make after each attack (or
+    // none, 3) once every throw and loss is paid for
+    fn priced_ahead(&self, prices: &[f64; 4]) -> Vec<[f64; 4]> {
+        let mut ahead = vec![[0.0; 4]; self.len() + 1];
+        for (round, theirs) in self.iter().enumerate().rev() {
+            for last in 0..4 {
+                ahead[round][last] = Attack::ALL
+                    .iter()
+                    .filter(|mine| mine.index() != last)
+                    .map(|mine| priced_score(theirs, mine, prices) + ahead[round + 1][mine.index()])
+                    .fold(f64::NEG_INFINITY, f64::max);
+            }
+        }
+        ahead
+    }
+
+    // the fewest and most losses the rest of the guide can take, after each attack
+    fn losses_ahead(&self) -> Vec<[(usize, usize); 4]> {
+        let mut ahead = vec![[(0, 0); 4]; self.len() + 1];
+        for (round, theirs) in self.iter().enumerate().rev() {
+            for last in 0..4 {
+                ahead[round][last] = Attack::ALL
+                    .iter()
+                    .filter(|mine| mine.index() != last)
+                    .map(|mine| {
+                        let lost = usize::from(theirs.beats() == *mine);
+                        let (least, most) = ahead[round + 1][mine.index()];
+                        (least + lost, most + lost)
+                    })
+                    .fold((usize::MAX, 0), |(least, most), (low, high)| {
+                        (least.min(low), most.max(high))
+                    });
+            }
+        }
+        ahead
+    }
+
+    // the losses of the rest of the guide taking turns between two attacks
+    fn alternating_losses(&self) -> Vec<[[usize; 3]; 3]> {
+        let mut ahead = vec![[[0; 3]; 3]; self.len() + 1];
+        for (round, theirs) in self.iter().enumerate().rev() {
+            let after = ahead[round + 1];
+            ahead[round] = [0, 1, 2].map(|first| {
+                let lost = usize::from(theirs.beats().index() == first);
+                [0, 1, 2].map(|second| lost + after[second][first])
+            });
+        }
+        ahead
+    }
+
+    // the bound the prices give, and the throws and losses of the guide behind it
+    fn priced_guide(&self, limits: &[Option<usize>; 4], prices: &[f64; 4]) -> (f64, [usize; 4]) {
+        let ahead = self.priced_ahead(prices);
+        let mut used = [0; 4];
+        let mut last = 3;
+        for (round, theirs) in self.iter().enumerate() {
+            let value =
+                |mine: &Attack| priced_score(theirs, mine, prices) + ahead[round + 1][mine.index()];
+            let mine = Attack::ALL
                 .into_iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<String>>();
-            let their_attack = match throws.first().to_attack() {
-                Ok(attack) => attack,
-                Err(err) => {
-                    tracing::error!("{err}");
-                    std::process::exit(1)
+                .filter(|mine| mine.index() != last)
+                .max_by(|a, b| value(a).total_cmp(&value(b)))
+                .unwrap_or(Attack::Rock);
+            used[mine.index()] += 1;
+            used[3] += usize::from(theirs.beats() == mine);
+            last = mine.index();
+        }
+        let paid = (0..4)
+            .filter_map(|index| limits[index].map(|limit| prices[index] * limit as f64))
+            .sum::<f64>();
+        (ahead[0][3] + paid, used)
+    }
+
+    // the best guide keeping `caps` and `losses` that scores at least
+    // `threshold`, with `width` keeping only that many partial guides a round
+    // instead of giving up when there are too many
+    fn search_in_order(
+        &self,
+        caps: &[Option<usize>; 3],
+        losses: Option<usize>,
+        prices: &[f64; 4],
+        threshold: i64,
+        width: Option<usize>,
+    ) -> Result<Option<Vec<Attack>>> {
+        // (last attack, losses so far, throws of each attack), only tracking the
+        // losses and throws that are constrained so the unconstrained search stays small
+        type State = (usize, usize, [usize; 3]);
+        let ahead = self.priced_ahead(prices);
+        let reach = self.losses_ahead();
+        let alternating = self.alternating_losses();
+        let mut kept = 0;
+        let mut current: Vec<(State, i64)> = vec![((3, 0, [0; 3]), 0)];
+        // the attack thrown and the state it followed, for every state of every round
+        let mut history: Vec<Vec<(u8, u32)>> = Vec::with_capacity(self.len());
+        for (round, theirs) in self.iter().enumerate() {
+            let left = self.len() - round - 1;
+            let mut seen: HashMap<State, usize> = HashMap::with_capacity(current.len() * 3);
+            let mut next: Vec<(State, i64)> = vec![];
+            let mut from: Vec<(u8, u32)> = vec![];
+            let mut bounds: Vec<f64> = vec![];
+            for (parent, ((last, lost, throws), total)) in current.iter().enumerate() {
+                for mine in Attack::ALL {
+                    let m = mine.index();
+                    if m == *last {
+                        continue;
+                    }
+                    let lost = match losses {
+                        Some(losses) if theirs.beats() == mine && *lost == losses => continue,
+                        Some(_) => lost + usize::from(theirs.beats() == mine),
+                        None => 0,
+                    };
+                    let mut throws = *throws;
+                    if let Some(cap) = caps[m] {
+                        if throws[m] == cap {
+                            continue;
+                        }
+                        throws[m] += 1;
+                    }
+                    // a count that can't reach the cap in the rounds left is as good
+                    // as any other, so they all share the lowest one that still can
+                    for (count, cap) in throws.iter_mut().zip(caps) {
+                        if let Some(cap) = cap {
+                            *count = (*count).max(cap.saturating_sub(left));
+                        }
+                    }
+                    // too few rounds left to lose enough, or to fill without going
+                    // over a cap or repeating, which any attack can manage in at most
+                    // every other round and the one just thrown in one fewer
+                    let room = (0..3)
+                        .map(|index| {
+                            let turns = if index == m { left / 2 } else { (left + 1) / 2 };
+                            caps[index].map_or(turns, |cap| (cap - throws[index]).min(turns))
+                        })
+                        .sum::<usize>();
+                    let (least, most) = reach[round + 1][m];
+                    if room < left
+                        || losses.map_or(false, |losses| {
+                            losses - lost < least || losses - lost > most
+                        })
+                    {
+                        continue;
+                    }
+                    // once an attack is used up the other two take turns, so the
+                    // rest of the guide is fixed but for which goes first
+                    if let Some(spent) = (0..3).find(|index| caps[*index] == Some(throws[*index])) {
+                        let firsts = if spent == m {
+                            [(spent + 1) % 3, (spent + 2) % 3]
+                        } else {
+                            [3 - m - spent; 2]
+                        };
+                        let fits = firsts.into_iter().any(|first| {
+                            let second = 3 - first - spent;
+                            let fill = |index: usize, turns: usize| {
+                                caps[index].map_or(true, |cap| throws[index] + turns <= cap)
+                            };
+                            fill(first, (left + 1) / 2)
+                                && fill(second, left / 2)
+                                && losses.map_or(true, |losses| {
+                                    lost + alternating[round + 1][first][second] == losses
+                                })
+                        });
+                        if !fits {
+                            continue;
+                        }
+                    }
+                    let total = total + throw_score(theirs, &mine);
+                    // the priced rest of the guide, paid back whatever throws and
+                    // losses are still allowed, bounds the best this can finish on
+                    let paid = (0..3)
+                        .filter_map(|index| {
+                            caps[index].map(|cap| prices[index] * (cap - throws[index]) as f64)
+                        })
+                        .chain(losses.map(|losses| prices[3] * (losses - lost) as f64))
+                        .sum::<f64>();
+                    let bound = total as f64 + ahead[round + 1][m] + paid;
+                    if bound < threshold as f64 - 1e-6 {
+                        continue;
+                    }
+                    let state = (m, lost, throws);
+                    match seen.get(&state) {
+                        Some(index) if next[*index].1 >= total => (),
+                        Some(index) => {
+                            next[*index].1 = total;
+                            from[*index] = (m as u8, u32::try_from(parent)?);
+                            bounds[*index] = bound;
+                        }
+                        None => {
+                            seen.insert(state, next.len());
+                            next.push((state, total));
+                            from.push((m as u8, u32::try_from(parent)?));
+                            bounds.push(bound);
+                        }
+                    }
                 }
-            };
-            let my_attack = match throws.last().to_attack() {
-                Ok(attack) => attack,
-                Err(err) => {
-                    tracing::error!("{err}");
-                    std::process::exit(1)
+            }
+            match width {
+                // only the partial guides with the best bounds carry on, a share
+                // for each loss count so some can still end on the right one
+                Some(width) if next.len() > width => {
+                    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+                    for (index, ((_, lost, _), _)) in next.iter().enumerate() {
+                        groups.entry(*lost).or_default().push(index);
+                    }
+                    let share = (width / groups.len()).max(1);
+                    let mut order = vec![];
+                    for mut group in groups.into_values() {
+                        if group.len() > share {
+                            group.select_nth_unstable_by(share - 1, |a, b| {
+                                bounds[*b].total_cmp(&bounds[*a])
+                            });
+                            group.truncate(share);
+                        }
+                        order.extend(group);
+                    }
+                    order.sort_unstable();
+                    next = order.iter().map(|index| next[*index]).collect();
+                    from = order.iter().map(|index| from[*index]).collect();
                 }
-            };
-            GameOutput::Part1((their_attack, my_attack))
+                Some(_) => (),
+                None => {
+                    kept += next.len();
+                    if kept > MAX_GUIDE_STATES {
+                        anyhow::bail!("too many partial guides to search");
+                    }
+                }
+            }
+            current = next;
+            history.push(from);
+        }
+        let best = current
+            .iter()
+            .enumerate()
+            .filter(|(_, ((_, lost, _), _))| losses.map_or(true, |losses| *lost == losses))
+            .max_by_key(|(_, (_, total))| *total);
+        // a guide below the threshold might lose to one that was pruned
+        let mut state = match best {
+            Some((state, (_, total))) if *total >= threshold => state,
+            _ => return Ok(None),
+        };
+        // walk the choices back from the final round
+        let mut guide = vec![Attack::Rock; self.len()];
+        for round in (0..self.len()).rev() {
+            let (mine, parent) = history[round][state];
+            guide[round] = Attack::ALL[usize::from(mine)];
+            state = parent as usize;
+        }
+        Ok(Some(guide))
+    }
+}
+
+// the guide written out in the part 1 `A X` format
+fn write_guide(theirs: &[Attack], mine: &[Attack]) -> Vec<String> {
+    let letter = |attack: &Attack, letters: [char; 3]| letters[attack.index()];
+    theirs
+        .iter()
+        .zip(mine)
+        .map(|(theirs, mine)| {
+            format!(
+                "{} {}",
+                letter(theirs, ['A', 'B', 'C']),
+                letter(mine, ['X', 'Y', 'Z'])
+            )
+        })
+        .collect()
+}
+
+// `--solve` with any of `--max-per-move N`, `--losses K` and `--no-repeat`
+fn constraints_from_args() -> Result<Constraints> {
+    let number = |flag: &str| -> Result<Option<usize>> {
+        flag_value(flag).map_or(Ok(None), |value| Ok(Some(value.parse()?)))
+    };
+    Ok(Constraints {
+        max_per_move: number("--max-per-move")?,
+        losses: number("--losses")?,
+        no_repeat: has_flag("--no-repeat"),
+    })
+}
+
+/// a tournament player, picks a throw given every round played so far as `(mine, theirs)`
+trait Bot {
+    fn name(&self) -> String;
+    fn throw(&mut self, history: &[(Attack, Attack)]) -> Attack;
+}
+
+/// uniformly random throws from a seeded xorshift generator
+struct RandomBot {
+    rng: Xorshift,
+}
+
+/// counters whatever the opponent has thrown most often
+struct FrequencyBot;
+
+/// repeats the opponent's previous throw
+struct CopycatBot;
+
+/// plays my column of a strategy guide, looping when it runs out
+struct GuideBot {
+    name: String,
+    guide: Vec<Attack>,
+}
+
+impl RandomBot {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Xorshift::new(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn throw(&mut self, _: &[(Attack, Attack)]) -> Attack {
+        Attack::ALL[(self.rng.next_u64() % 3) as usize]
+    }
+}
+
+impl Bot for FrequencyBot {
+    fn name(&self) -> String {
+        "frequency".to_string()
+    }
+
+    fn throw(&mut self, history: &[(Attack, Attack)]) -> Attack {
+        let mut seen = [0; 3];
+        for (_, theirs) in history {
+            seen[theirs.index()] += 1;
+        }
+        // ties go to the earliest attack, so the first round is always paper
+        let favourite = Attack::ALL
+            .into_iter()
+            .rev()
+            .max_by_key(|attack| seen[attack.index()])
+            .unwrap_or(Attack::Rock);
+        favourite.beaten_by()
+    }
+}
+
+impl Bot for CopycatBot {
+    fn name(&self) -> String {
+        "copycat".to_string()
+    }
+
+    fn throw(&mut self, history: &[(Attack, Attack)]) -> Attack {
+        history.last().map_or(Attack::Rock, |(_, theirs)| *theirs)
+    }
+}
+
+impl Bot for GuideBot {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn throw(&mut self, history: &[(Attack, Attack)]) -> Attack {
+        self.guide[history.len() % self.guide.len()]
+    }
+}
+
+/// one row of the tournament leaderboard
+#[derive(Debug, Clone, Default)]
+struct Standing {
+    name: String,
+    score: u64,
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Standing {
+    fn record(&mut self, round: &GameOutput, outcome: &GameOutcome) {
+        self.score += u64::from(round.round_score());
+        match outcome {
+            GameOutcome::Win => self.wins += 1,
+            GameOutcome::Draw => self.draws += 1,
+            GameOutcome::Lose => self.losses += 1,
+        }
+    }
+
+    fn rate(&self, count: u32) -> f64 {
+        let played = self.wins + self.draws + self.losses;
+        if played == 0 {
+            return 0.0;
+        }
+        f64::from(count) * 100.0 / f64::from(played)
+    }
+}
+
+/// every bot plays every other bot once for `rounds` rounds
+///
+/// `roster` builds fresh bots for each match so no state leaks between opponents
+fn tournament(roster: &dyn Fn() -> Vec<Box<dyn Bot>>, rounds: usize) -> Vec<Standing> {
+    let mut standings = roster()
+        .iter()
+        .map(|bot| Standing {
+            name: bot.name(),
+            ..Standing::default()
         })
         .collect::<Vec<_>>();
+    for first in 0..standings.len() {
+        for second in first + 1..standings.len() {
+            let (mut a, mut b) = (roster().swap_remove(first), roster().swap_remove(second));
+            let mut history_a = Vec::with_capacity(rounds);
+            let mut history_b = Vec::with_capacity(rounds);
+            for _ in 0..rounds {
+                let (throw_a, throw_b) = (a.throw(&history_a), b.throw(&history_b));
+                standings[first].record(
+                    &GameOutput::Part1((throw_b, throw_a)),
+                    &throw_a.against(&throw_b),
+                );
+                standings[second].record(
+                    &GameOutput::Part1((throw_a, throw_b)),
+                    &throw_b.against(&throw_a),
+                );
+                history_a.push((throw_a, throw_b));
+                history_b.push((throw_b, throw_a));
+            }
+        }
+    }
+    standings.sort_by_key(|standing| std::cmp::Reverse(standing.score));
+    standings
+}
+
+// the default bots, with both readings of the input guide entered as fixed strategies
+fn default_roster(seed: u64, lines: &[&str]) -> Result<impl Fn() -> Vec<Box<dyn Bot>>> {
+    let part1 = play_guide(lines, &StrategyKey::part1())?;
+    let part2 = play_guide(lines, &StrategyKey::part2())?;
+    let column = |rounds: &[GameOutput]| -> Vec<Attack> {
+        rounds
+            .iter()
+            .map(|round| match round {
+                GameOutput::Part1((_, mine)) => *mine,
+                GameOutput::Part2((theirs, outcome)) => match outcome {
+                    GameOutcome::Win => theirs.beaten_by(),
+                    GameOutcome::Draw => *theirs,
+                    GameOutcome::Lose => theirs.beats(),
+                },
+            })
+            .collect()
+    };
+    let (moves, outcomes) = (column(&part1), column(&part2));
+    Ok(move || -> Vec<Box<dyn Bot>> {
+        vec![
+            Box::new(RandomBot::new(seed)),
+            Box::new(FrequencyBot),
+            Box::new(CopycatBot),
+            Box::new(GuideBot {
+                name: "guide (moves)".to_string(),
+                guide: moves.clone(),
+            }),
+            Box::new(GuideBot {
+                name: "guide (outcomes)".to_string(),
+                guide: outcomes.clone(),
+            }),
+        ]
+    })
+}
+
+/// one round of `--explain` output
+#[derive(Debug, Serialize)]
+struct ExplainedRound {
+    // the key the guide was read with, telling part 1 and part 2 rows apart
+    key: String,
+    round: usize,
+    opponent: Attack,
+    // my attack in part 1, the outcome i need in part 2
+    column: String,
+    throw: Attack,
+    outcome: GameOutcome,
+    shape_points: u32,
+    outcome_points: u32,
+    score: u32,
+}
+
+/// totals printed after the explained rounds
+#[derive(Debug, Default, Serialize)]
+struct ExplainSummary {
+    key: String,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    total: u32,
+}
+
+trait Explain {
+    fn explain(&self, key: &str) -> (Vec<ExplainedRound>, ExplainSummary);
+}
+
+impl Explain for [GameOutput] {
+    fn explain(&self, key: &str) -> (Vec<ExplainedRound>, ExplainSummary) {
+        let mut summary = ExplainSummary {
+            key: key.to_string(),
+            ..ExplainSummary::default()
+        };
+        let rounds = self
+            .iter()
+            .enumerate()
+            .map(|(index, round)| {
+                let (opponent, column, throw) = match round {
+                    GameOutput::Part1((theirs, mine)) => (*theirs, format!("{mine:?}"), *mine),
+                    GameOutput::Part2((theirs, outcome)) => {
+                        let mine = match outcome {
+                            GameOutcome::Win => theirs.beaten_by(),
+                            GameOutcome::Draw => *theirs,
+                            GameOutcome::Lose => theirs.beats(),
+                        };
+                        (*theirs, format!("{outcome:?}"), mine)
+                    }
+                };
+                let outcome = throw.against(&opponent);
+                match outcome {
+                    GameOutcome::Win => summary.wins += 1,
+                    GameOutcome::Draw => summary.draws += 1,
+                    GameOutcome::Lose => summary.losses += 1,
+                }
+                let score = round.round_score();
+                summary.total += score;
+                // shape is 1 for rock through 3 for scissors
+                let shape_points = match throw {
+                    Attack::Rock => 1,
+                    Attack::Paper => 2,
+                    Attack::Scissors => 3,
+                };
+                ExplainedRound {
+                    key: key.to_string(),
+                    round: index + 1,
+                    opponent,
+                    column,
+                    throw,
+                    outcome,
+                    shape_points,
+                    outcome_points: score - shape_points,
+                    score,
+                }
+            })
+            .collect();
+        (rounds, summary)
+    }
+}
+
+// `--explain`, as an aligned table or `--format json` lines
+fn print_explained(rounds: &[GameOutput], key: &StrategyKey, json: bool) -> Result<()> {
+    let (rounds, summary) = rounds.explain(&key.describe());
+    if json {
+        for round in &rounds {
+            println!("{}", serde_json::to_string(round)?);
+        }
+        println!("{}", serde_json::to_string(&summary)?);
+        return Ok(());
+    }
+    println!(
+        "{:>5}  {:<8}  {:<8}  {:<8}  {:<7}  {:>9}  {:>11}  {:>5}",
+        "round", "opponent", "column", "throw", "outcome", "shape pts", "outcome pts", "score"
+    );
+    for round in &rounds {
+        println!(
+            "{:>5}  {:<8}  {:<8}  {:<8}  {:<7}  {:>9}  {:>11}  {:>5}",
+            round.round,
+            format!("{:?}", round.opponent),
+            round.column,
+            format!("{:?}", round.throw),
+            format!("{:?}", round.outcome),
+            round.shape_points,
+            round.outcome_points,
+            round.score
+        );
+    }
+    println!(
+        "wins: {}, draws: {}, losses: {}, total: {}",
+        summary.wins, summary.draws, summary.losses, summary.total
+    );
+    Ok(())
+}
+
+// part 1 and part 2 round scores, indexed by `(opponent - 'A') * 3 + (column - 'X')`
+const PART1_SCORES: [u32; 9] = [4, 8, 3, 1, 5, 9, 7, 2, 6];
+const PART2_SCORES: [u32; 9] = [3, 4, 8, 1, 5, 9, 2, 6, 7];
+
+// every `"A X\n"` line is exactly 4 bytes
+const RECORD: usize = 4;
+// records summed side by side per batch, wide enough for the compiler to vectorise
+const LANES: usize = 16;
+// records per parallel block
+const BLOCK: usize = 64 * 1024;
+
+// score and validity of a single record, without branching on its contents
+#[inline(always)]
+fn score_record(record: &[u8], table: &[u32; 9]) -> (u32, u32) {
+    let theirs = record[0].wrapping_sub(b'A');
+    let mine = record[2].wrapping_sub(b'X');
+    let invalid = u32::from(theirs > 2)
+        | u32::from(mine > 2)
+        | u32::from(record[1] != b' ')
+        | u32::from(record[3] != b'\n');
+    // clamp so a bad record still indexes the table, it's reported through `invalid`
+    let index = usize::from(theirs.min(2)) * 3 + usize::from(mine.min(2));
+    (table[index], invalid)
+}
+
+fn score_block(block: &[u8], table: &[u32; 9]) -> (u64, u32) {
+    let mut lanes = [0_u32; LANES];
+    let mut invalid = 0;
+    let batches = block.chunks_exact(RECORD * LANES);
+    let remainder = batches.remainder();
+    for batch in batches {
+        for (lane, record) in lanes.iter_mut().zip(batch.chunks_exact(RECORD)) {
+            let (score, bad) = score_record(record, table);
+            *lane += score;
+            invalid |= bad;
+        }
+    }
+    let mut total = lanes.iter().map(|lane| u64::from(*lane)).sum::<u64>();
+    for record in remainder.chunks_exact(RECORD) {
+        let (score, bad) = score_record(record, table);
+        total += u64::from(score);
+        invalid |= bad;
+    }
+    (total, invalid)
+}
+
+/// byte level game tally for `\n` separated `"A X"` records, no per line allocation
+///
+/// `table` is [`PART1_SCORES`] or [`PART2_SCORES`]
+fn fast_tally(input: &[u8], table: &[u32; 9]) -> Result<u64> {
+    // the final line may be missing its newline
+    let body_len = input.len() - input.len() % RECORD;
+    let (body, tail) = input.split_at(body_len);
+    let (total, invalid) = body
+        .par_chunks(RECORD * BLOCK)
+        .map(|block| score_block(block, table))
+        .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 | b.1));
+    let (tail_score, tail_invalid) = match tail.len() {
+        0 => (0, 0),
+        3 => score_record(&[tail[0], tail[1], tail[2], b'\n'], table),
+        _ => (0, 1),
+    };
+    if invalid | tail_invalid != 0 {
+        anyhow::bail!("input isn't made of 4 byte `A X` records");
+    }
+    Ok(total + u64::from(tail_score))
+}
+
+fn part1(lines: &[&str]) -> Vec<GameOutput> {
+    let start = std::time::Instant::now();
+    let rounds = match play_guide(lines, &StrategyKey::part1()) {
+        Ok(rounds) => rounds,
+        Err(err) => {
+            tracing::error!("{err}");
+            std::process::exit(1)
+        }
+    };
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     rounds
 }
 
+// for pt 2, we discover our input key was actually game result
 fn part2(lines: &[&str]) -> Vec<GameOutput> {
     let start = std::time::Instant::now();
-    let rounds = lines
-        .par_iter()
-        .map(|line| -> GameOutput {
-            let throws = line
-                .split_whitespace()
-                .into_iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<String>>();
-            let their_attack = match throws.first().to_attack() {
-                Ok(attack) => attack,
-                Err(err) => {
-                    tracing::error!("{err}");
-                    std::process::exit(1)
-                }
-            };
-            let game_outcome = match throws.last().to_game_result() {
-                Ok(outcome) => outcome,
-                Err(err) => {
-                    tracing::error!("{err}");
-                    std::process::exit(1)
-                }
-            };
-            GameOutput::Part2((their_attack, game_outcome))
-        })
-        .collect::<Vec<_>>();
+    let rounds = match play_guide(lines, &StrategyKey::part2()) {
+        Ok(rounds) => rounds,
+        Err(err) => {
+            tracing::error!("{err}");
+            std::process::exit(1)
+        }
+    };
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     rounds
 }
 
+// `--key <spec>` or `--key-file <path>` picks the guide interpretation
+fn key_from_args() -> Result<Option<StrategyKey>> {
+    if let Some(spec) = flag_value("--key") {
+        return StrategyKey::parse(&spec).map(Some);
+    }
+    if let Some(path) = flag_value("--key-file") {
+        return StrategyKey::parse(&std::fs::read_to_string(path)?).map(Some);
+    }
+    Ok(None)
+}
+
 pub fn main() -> Result<()> {
     init_logger();
     let lines = INPUT.par_lines().collect::<Vec<_>>();
+    let key = key_from_args()?;
+    if has_flag("--permutations") {
+        let keys = key.map_or_else(
+            || vec![StrategyKey::part1(), StrategyKey::part2()],
+            |key| vec![key],
+        );
+        for key in keys.iter().flat_map(StrategyKey::permutations) {
+            let total = play_guide(&lines, &key)?.game_tally();
+            println!("{}\t{total}", key.describe());
+        }
+        return Ok(());
+    }
+    if has_flag("--explain") {
+        let json = flag_value("--format").as_deref() == Some("json");
+        let keys = key.map_or_else(
+            || vec![StrategyKey::part1(), StrategyKey::part2()],
+            |key| vec![key],
+        );
+        for key in &keys {
+            if !json {
+                println!("{}", key.describe());
+            }
+            print_explained(&play_guide(&lines, key)?, key, json)?;
+        }
+        return Ok(());
+    }
+    if has_flag("--fast") {
+        // `--input <path>` for guides far too big to build into the binary
+        let input = match flag_value("--input") {
+            Some(path) => std::fs::read(path)?,
+            None => INPUT.as_bytes().to_vec(),
+        };
+        println!("{}", fast_tally(&input, &PART1_SCORES)?);
+        println!("{}", fast_tally(&input, &PART2_SCORES)?);
+        return Ok(());
+    }
+    if has_flag("--tournament") {
+        let rounds = flag_value("--rounds").map_or(Ok(1000), |rounds| rounds.parse())?;
+        let seed = flag_value("--seed").map_or(Ok(2022), |seed| seed.parse())?;
+        let standings = tournament(&default_roster(seed, &lines)?, rounds);
+        println!("bot\tscore\twin %\tdraw %\tloss %");
+        for standing in &standings {
+            println!(
+                "{}\t{}\t{:.1}\t{:.1}\t{:.1}",
+                standing.name,
+                standing.score,
+                standing.rate(standing.wins),
+                standing.rate(standing.draws),
+                standing.rate(standing.losses)
+            );
+        }
+        return Ok(());
+    }
+    if has_flag("--solve") {
+        let theirs = key
+            .unwrap_or_else(StrategyKey::part1)
+            .opponent_column(&lines)?;
+        let mine = theirs.solve_guide(&constraints_from_args()?)?;
+        let guide = write_guide(&theirs, &mine);
+        for line in &guide {
+            println!("{line}");
+        }
+        let guide = guide.iter().map(String::as_str).collect::<Vec<_>>();
+        let total = play_guide(&guide, &StrategyKey::part1())?.game_tally();
+        eprintln!("score: {total}");
+        return Ok(());
+    }
+    if let Some(key) = key {
+        let total = play_guide(&lines, &key)?.game_tally();
+        println!("{}\t{total}", key.describe());
+        return Ok(());
+    }
     let total = part1(&lines).game_tally();
     tracing::info!("initial game info total: {total:#?}");
     let total = part2(&lines).game_tally();
@@ -237,4 +1481,245 @@ pub mod tests {
         assert_eq!(total, 12989);
         Ok(())
     }
+
+    #[test]
+    fn day2_key_mapping_tests() -> Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let key = StrategyKey::parse("A=rock,B=paper,C=scissors;X=lose,Y=draw,Z=win")?;
+        assert_eq!(play_guide(&lines, &key)?.game_tally(), 12989);
+        // the default readings are among their own permutations
+        let totals = StrategyKey::part1()
+            .permutations()
+            .iter()
+            .map(|key| play_guide(&lines, key).map(|rounds| rounds.game_tally()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(totals.len(), 6);
+        assert!(totals.contains(&15691));
+        assert!(StrategyKey::parse("A=rock,B=rock,C=scissors;X=lose,Y=draw,Z=win").is_err());
+        assert!(StrategyKey::parse("A=rock,B=paper,C=scissors").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day2_solver_tests() -> Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let theirs = StrategyKey::part1().opponent_column(&lines)?;
+        let constraints = Constraints {
+            max_per_move: Some(900),
+            losses: Some(10),
+            no_repeat: false,
+        };
+        let mine = theirs.solve_guide(&constraints)?;
+        let guide = write_guide(&theirs, &mine);
+        let guide = guide.iter().map(String::as_str).collect::<Vec<_>>();
+        let total = play_guide(&guide, &StrategyKey::part1())?.game_tally();
+        let expected = theirs
+            .iter()
+            .zip(&mine)
+            .map(|(theirs, mine)| throw_score(theirs, mine))
+            .sum::<i64>();
+        assert_eq!(i64::from(total), expected);
+        assert!(Attack::ALL
+            .iter()
+            .all(|attack| mine.iter().filter(|m| *m == attack).count() <= 900));
+        // a cap tight enough to bind without repeats, which the priced bound proves optimal
+        let constraints = Constraints {
+            max_per_move: Some(834),
+            no_repeat: true,
+            ..Constraints::default()
+        };
+        let mine = theirs.solve_guide(&constraints)?;
+        assert!(mine.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(Attack::ALL
+            .iter()
+            .all(|attack| mine.iter().filter(|m| *m == attack).count() <= 834));
+        let total = theirs
+            .iter()
+            .zip(&mine)
+            .map(|(theirs, mine)| throw_score(theirs, mine))
+            .sum::<i64>();
+        assert_eq!(total, 17097);
+        // compare against every possible guide for a short game
+        let short = &theirs[..7];
+        let every_guide = (0..3_usize.pow(7)).map(|mut code| {
+            (0..7)
+                .map(|_| {
+                    let attack = Attack::ALL[code % 3];
+                    code /= 3;
+                    attack
+                })
+                .collect::<Vec<_>>()
+        });
+        let all_constraints = [
+            Constraints::default(),
+            Constraints {
+                max_per_move: Some(3),
+                ..Constraints::default()
+            },
+            Constraints {
+                max_per_move: Some(3),
+                losses: Some(2),
+                no_repeat: false,
+            },
+            Constraints {
+                no_repeat: true,
+                ..Constraints::default()
+            },
+            Constraints {
+                losses: Some(3),
+                no_repeat: true,
+                ..Constraints::default()
+            },
+            Constraints {
+                max_per_move: Some(3),
+                no_repeat: true,
+                ..Constraints::default()
+            },
+            Constraints {
+                max_per_move: Some(3),
+                losses: Some(1),
+                no_repeat: true,
+            },
+            // seven rounds can't be split three ways with at most two of each
+            Constraints {
+                max_per_move: Some(2),
+                no_repeat: true,
+                ..Constraints::default()
+            },
+        ];
+        for constraints in &all_constraints {
+            let allowed = |mine: &Vec<Attack>| {
+                let lost = short
+                    .iter()
+                    .zip(mine)
+                    .filter(|(theirs, mine)| theirs.beats() == **mine)
+                    .count();
+                constraints.max_per_move.map_or(true, |max| {
+                    Attack::ALL
+                        .iter()
+                        .all(|attack| mine.iter().filter(|m| *m == attack).count() <= max)
+                }) && constraints.losses.map_or(true, |losses| lost == losses)
+                    && (!constraints.no_repeat || mine.windows(2).all(|pair| pair[0] != pair[1]))
+            };
+            let score = |mine: &Vec<Attack>| {
+                short
+                    .iter()
+                    .zip(mine)
+                    .map(|(theirs, mine)| throw_score(theirs, mine))
+                    .sum::<i64>()
+            };
+            let best = every_guide
+                .clone()
+                .filter(allowed)
+                .map(|mine| score(&mine))
+                .max();
+            match best {
+                Some(best) => {
+                    let solved = short.solve_guide(constraints)?;
+                    assert!(allowed(&solved));
+                    assert_eq!(score(&solved), best);
+                }
+                None => assert!(short.solve_guide(constraints).is_err()),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn day2_tournament_tests() -> Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let standings = tournament(&default_roster(7, &lines)?, 300);
+        assert_eq!(standings.len(), 5);
+        for standing in &standings {
+            assert_eq!(standing.wins + standing.draws + standing.losses, 4 * 300);
+        }
+        let wins = standings.iter().map(|s| s.wins).sum::<u32>();
+        let losses = standings.iter().map(|s| s.losses).sum::<u32>();
+        assert_eq!(wins, losses);
+        // a fixed bot gets exploited by the frequency counter
+        let roster = || -> Vec<Box<dyn Bot>> {
+            vec![
+                Box::new(FrequencyBot),
+                Box::new(GuideBot {
+                    name: "rock".to_string(),
+                    guide: vec![Attack::Rock],
+                }),
+            ]
+        };
+        let standings = tournament(&roster, 100);
+        assert_eq!(standings[0].name, "frequency");
+        assert_eq!(standings[0].wins, 100);
+        assert_eq!(standings[0].score, 100 * 8);
+        Ok(())
+    }
+
+    #[test]
+    fn day2_fast_tally_tests() -> Result<()> {
+        init_logger();
+        // every table entry agrees with the enum scoring
+        for theirs in Attack::ALL {
+            for (column, outcome) in StrategyKey::OUTCOMES.iter().enumerate() {
+                let index = theirs.index() * 3 + column;
+                let mine = Attack::ALL[column];
+                assert_eq!(
+                    PART1_SCORES[index],
+                    GameOutput::Part1((theirs, mine)).round_score()
+                );
+                assert_eq!(
+                    PART2_SCORES[index],
+                    GameOutput::Part2((theirs, outcome.clone())).round_score()
+                );
+            }
+        }
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let input = INPUT.as_bytes();
+        assert_eq!(fast_tally(input, &PART1_SCORES)?, 15691);
+        assert_eq!(fast_tally(input, &PART2_SCORES)?, 12989);
+        // enough records to span several parallel blocks
+        let repeated = [INPUT; 100].join("\n");
+        let repeated_lines = repeated.par_lines().collect::<Vec<_>>();
+        assert_eq!(
+            fast_tally(repeated.as_bytes(), &PART1_SCORES)?,
+            u64::from(part1(&repeated_lines).game_tally())
+        );
+        assert_eq!(
+            fast_tally(format!("{repeated}\n").as_bytes(), &PART2_SCORES)?,
+            u64::from(part2(&repeated_lines).game_tally())
+        );
+        assert_eq!(lines.len() * 100, repeated_lines.len());
+        assert!(fast_tally(b"A X\nD X\n", &PART1_SCORES).is_err());
+        assert!(fast_tally(b"A X\r\nB Y", &PART1_SCORES).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day2_explain_tests() -> Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        for (rounds, total) in [(part1(&lines), 15691), (part2(&lines), 12989)] {
+            let (explained, summary) = rounds.explain("key");
+            assert_eq!(explained.len(), lines.len());
+            assert_eq!(summary.wins + summary.draws + summary.losses, lines.len());
+            assert_eq!(summary.total, total);
+            assert!(explained
+                .iter()
+                .all(|round| round.shape_points + round.outcome_points == round.score));
+        }
+        // "A Z" is rock against scissors in part 1, rock needing a win in part 2
+        let (explained, _) = part1(&["A Z"]).explain("part 1");
+        assert_eq!(explained[0].outcome, GameOutcome::Lose);
+        assert_eq!(explained[0].score, 3);
+        let (explained, summary) = part2(&["A Z"]).explain("part 2");
+        assert_eq!(explained[0].throw, Attack::Paper);
+        // json rows and summaries both say which key they came from
+        let row = serde_json::to_value(&explained[0])?;
+        assert_eq!(row["key"], "part 2");
+        assert_eq!(serde_json::to_value(&summary)?["key"], "part 2");
+        assert_eq!(explained[0].column, "Win");
+        assert_eq!(explained[0].outcome_points, 6);
+        Ok(())
+    }
 }
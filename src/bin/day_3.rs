@@ -1,174 +1,774 @@
-use advent_of_code_2022::utils::init_logger;
+use advent_of_code_2022::utils::{flag_value, has_flag, init_logger};
+use anyhow::Result;
 use rayon::{
-    prelude::{
-        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
-    },
-    slice::ParallelSlice,
-    str::{Chars as ParChars, ParallelString},
+    prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    str::ParallelString,
 };
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 
 const INPUT: &str = include_str!("../../inputs/day3.txt");
 
-const ALPHABET: [char; 52] = [
-    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
-    't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L',
-    'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
-];
+/// set of rucksack items, bit `n` is set when the item with index `n` is held
+///
+/// the default scheme's priorities fit in 1..=52 so a single `u64` covers every item,
+/// larger schemes use more words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ItemSet<const WORDS: usize = 1>([u64; WORDS]);
+
+/// how items map to bits of an [`ItemSet`] and what each one is worth
+trait PriorityScheme: Sync {
+    // bit the item occupies, starting from 1
+    fn index(&self, item: char) -> Option<u32>;
+    fn item(&self, index: u32) -> Option<char>;
+    // score of the item at `index`
+    fn weight(&self, index: u32) -> u32 {
+        index
+    }
+    // number of items, which is also the highest index
+    fn len(&self) -> u32;
+}
+
+/// Lowercase item types `a` through `z` have priorities 1 through 26
+///
+/// Uppercase item types `A` through `Z` have priorities 27 through 52
+struct Letters;
+
+/// letters as usual, then digits `0` through `9` as 53 through 62
+struct Alphanumeric;
 
-type LookupType = BTreeMap<char, u32>;
+/// any symbols, indexed in the order given, with an optional weight each
+#[derive(Debug, Clone, Default)]
+struct SymbolTable {
+    items: Vec<char>,
+    indexes: HashMap<char, u32>,
+    weights: Vec<u32>,
+}
+
+// widest set is 16 words, bit 0 is never used
+const MAX_ITEMS: usize = 16 * 64 - 1;
 
 trait CharHelpers {
+    fn priority(&self) -> Option<u32>;
     fn score(&self) -> u32;
 }
 
-trait TupleCharSearcher {
-    fn find_char_intersection(&self) -> u32;
+impl CharHelpers for char {
+    fn priority(&self) -> Option<u32> {
+        match self {
+            'a'..='z' => Some(*self as u32 - 'a' as u32 + 1),
+            'A'..='Z' => Some(*self as u32 - 'A' as u32 + 27),
+            _ => None,
+        }
+    }
+
+    fn score(&self) -> u32 {
+        self.priority().unwrap_or_else(|| {
+            tracing::error!("no char found");
+            std::process::exit(1)
+        })
+    }
+}
+
+impl PriorityScheme for Letters {
+    fn index(&self, item: char) -> Option<u32> {
+        item.priority()
+    }
+
+    fn item(&self, index: u32) -> Option<char> {
+        match index {
+            1..=26 => char::from_u32('a' as u32 + index - 1),
+            27..=52 => char::from_u32('A' as u32 + index - 27),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> u32 {
+        52
+    }
+}
+
+impl PriorityScheme for Alphanumeric {
+    fn index(&self, item: char) -> Option<u32> {
+        match item {
+            '0'..='9' => Some(item as u32 - '0' as u32 + 53),
+            _ => item.priority(),
+        }
+    }
+
+    fn item(&self, index: u32) -> Option<char> {
+        match index {
+            53..=62 => char::from_u32('0' as u32 + index - 53),
+            _ => Letters.item(index),
+        }
+    }
+
+    fn len(&self) -> u32 {
+        62
+    }
+}
+
+impl SymbolTable {
+    // every char is an item, weighted by its position
+    fn from_symbols(symbols: &str) -> Result<Self> {
+        symbols
+            .chars()
+            .filter(|symbol| !symbol.is_whitespace())
+            .try_fold(Self::default(), |table, symbol| table.with(symbol, None))
+    }
+
+    // one `symbol [weight]` per line, `#` starts a comment
+    fn parse(table: &str) -> Result<Self> {
+        table
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .try_fold(Self::default(), |table, line| {
+                let mut parts = line.split_whitespace();
+                let symbol = parts.next().unwrap_or_default();
+                let mut chars = symbol.chars();
+                let item = match (chars.next(), chars.next()) {
+                    (Some(item), None) => item,
+                    _ => anyhow::bail!("expected a single symbol, found {symbol}"),
+                };
+                let weight = parts.next().map(str::parse::<u32>).transpose()?;
+                table.with(item, weight)
+            })
+    }
+
+    fn with(mut self, item: char, weight: Option<u32>) -> Result<Self> {
+        if self.items.len() == MAX_ITEMS {
+            anyhow::bail!("a scheme can hold at most {MAX_ITEMS} items");
+        }
+        let index = self.items.len() as u32 + 1;
+        if self.indexes.insert(item, index).is_some() {
+            anyhow::bail!("{item} is listed more than once");
+        }
+        self.items.push(item);
+        self.weights.push(weight.unwrap_or(index));
+        Ok(self)
+    }
 }
 
-trait ParCharsHelpers {
-    fn fold_to_lookup(&self) -> LookupType;
-    fn fold_to_duplicate_lookup(&self, target: &LookupType) -> LookupType;
+impl PriorityScheme for SymbolTable {
+    fn index(&self, item: char) -> Option<u32> {
+        self.indexes.get(&item).copied()
+    }
+
+    fn item(&self, index: u32) -> Option<char> {
+        self.items.get(index.checked_sub(1)? as usize).copied()
+    }
+
+    fn weight(&self, index: u32) -> u32 {
+        index
+            .checked_sub(1)
+            .and_then(|at| self.weights.get(at as usize))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn len(&self) -> u32 {
+        self.items.len() as u32
+    }
 }
 
-impl TupleCharSearcher for (&str, &str) {
-    fn find_char_intersection(&self) -> u32 {
-        let mut count = 0;
-        for char in self.0.chars() {
-            if self.1.contains(char) {
-                count += char.score();
-                return count;
+// picks the narrowest `ItemSet` that holds every item in the scheme
+macro_rules! with_item_set {
+    ($scheme:expr, $words:ident => $body:expr) => {
+        match $scheme.len() {
+            0..=63 => {
+                const $words: usize = 1;
+                $body
+            }
+            64..=255 => {
+                const $words: usize = 4;
+                $body
+            }
+            _ => {
+                const $words: usize = 16;
+                $body
             }
         }
-        count
+    };
+}
+
+impl From<&str> for ItemSet {
+    fn from(items: &str) -> Self {
+        Self([items.chars().fold(0, |set, item| set | 1 << item.score())])
     }
 }
 
-impl CharHelpers for char {
-    fn score(&self) -> u32 {
-        ALPHABET
+impl<const WORDS: usize> Default for ItemSet<WORDS> {
+    fn default() -> Self {
+        Self([0; WORDS])
+    }
+}
+
+impl<const WORDS: usize> ItemSet<WORDS> {
+    // items of `scheme`, skipping anything it doesn't know instead of exiting
+    fn with_scheme(items: &str, scheme: &dyn PriorityScheme) -> Self {
+        let mut set = Self::default();
+        for index in items.chars().filter_map(|item| scheme.index(item)) {
+            set.0[index as usize / 64] |= 1 << (index % 64);
+        }
+        set
+    }
+
+    fn zip(self, other: Self, op: impl Fn(u64, u64) -> u64) -> Self {
+        let mut set = self;
+        for (word, other) in set.0.iter_mut().zip(other.0) {
+            *word = op(*word, other);
+        }
+        set
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        self.zip(other, |a, b| a & b)
+    }
+
+    fn difference(self, other: Self) -> Self {
+        self.zip(other, |a, b| a & !b)
+    }
+
+    fn len(self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    // lowest index held, if any
+    fn first(self) -> Option<u32> {
+        self.iter().next()
+    }
+
+    // indexes held, lowest first
+    fn iter(self) -> impl Iterator<Item = u32> {
+        self.0.into_iter().enumerate().flat_map(|(word, mut bits)| {
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let index = bits.trailing_zeros();
+                // clear the lowest set bit
+                bits &= bits - 1;
+                Some(word as u32 * 64 + index)
+            })
+        })
+    }
+
+    fn weight_sum(self, scheme: &dyn PriorityScheme) -> u32 {
+        self.iter().map(|index| scheme.weight(index)).sum()
+    }
+
+    fn items(self, scheme: &dyn PriorityScheme) -> Vec<char> {
+        self.iter()
+            .map(|index| scheme.item(index).unwrap_or('?'))
+            .collect()
+    }
+
+    fn union(self, other: Self) -> Self {
+        self.zip(other, |a, b| a | b)
+    }
+
+    fn contains(self, index: u32) -> bool {
+        let word = index as usize / 64;
+        word < WORDS && self.0[word] & 1 << (index % 64) != 0
+    }
+
+    fn is_empty(self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+}
+
+// split on chars, a byte split could land inside a multi byte item
+fn compartments(line: &str) -> (&str, &str) {
+    let middle = line.chars().count() / 2;
+    line.split_at(
+        line.char_indices()
+            .nth(middle)
+            .map_or(line.len(), |(at, _)| at),
+    )
+}
+
+/// how elves are grouped when looking for badges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grouping {
+    // consecutive, non overlapping groups of this many elves
+    Chunks(usize),
+    // every run of this many consecutive elves
+    Sliding(usize),
+}
+
+impl Grouping {
+    const fn size(self) -> usize {
+        match self {
+            Self::Chunks(size) | Self::Sliding(size) => size,
+        }
+    }
+
+    // index of the first elf in every group, a short final chunk is kept
+    fn starts(self, elves: usize) -> Vec<usize> {
+        match self {
+            Self::Chunks(0) | Self::Sliding(0) => vec![],
+            Self::Chunks(size) => (0..elves).step_by(size).collect(),
+            Self::Sliding(size) if elves < size => vec![0],
+            Self::Sliding(size) => (0..=elves - size).collect(),
+        }
+    }
+}
+
+/// the one item a whole group carries
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Badge {
+    // 1 based line of the group's first elf
+    line: usize,
+    item: char,
+    priority: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GroupError {
+    ZeroSize,
+    // the input ends part way through a group
+    Incomplete {
+        line: usize,
+        found: usize,
+        size: usize,
+    },
+    NoBadge {
+        line: usize,
+    },
+    MultipleBadges {
+        line: usize,
+        items: Vec<char>,
+    },
+}
+
+impl std::fmt::Display for GroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroSize => write!(f, "group size must be at least 1"),
+            Self::Incomplete { line, found, size } => write!(
+                f,
+                "group starting at line {line} has {found} of {size} elves"
+            ),
+            Self::NoBadge { line } => write!(f, "group starting at line {line} shares no item"),
+            Self::MultipleBadges { line, items } => write!(
+                f,
+                "group starting at line {line} shares {} items: {}",
+                items.len(),
+                items.iter().collect::<String>()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+trait GroupHelpers {
+    fn find_badges(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Result<Vec<Badge>, GroupError>;
+    fn find_badges_with<const WORDS: usize>(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Result<Vec<Badge>, GroupError>;
+}
+
+impl GroupHelpers for [&str] {
+    fn find_badges(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Result<Vec<Badge>, GroupError> {
+        with_item_set!(scheme, WORDS => self.find_badges_with::<WORDS>(grouping, scheme))
+    }
+
+    fn find_badges_with<const WORDS: usize>(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Result<Vec<Badge>, GroupError> {
+        let size = grouping.size();
+        if size == 0 {
+            return Err(GroupError::ZeroSize);
+        }
+        // checked in parallel, the first error in input order is reported
+        grouping
+            .starts(self.len())
             .par_iter()
-            .position_last(|a| a == self)
-            .map_or_else(
-                || {
-                    tracing::error!("no char found");
-                    std::process::exit(1)
-                },
-                |score| match (score + 1).try_into() {
-                    Ok(score) => score,
-                    Err(err) => {
-                        tracing::error!("unable to cast score {err}");
-                        std::process::exit(1)
-                    }
-                },
-            )
-    }
-}
-
-impl ParCharsHelpers for ParChars<'_> {
-    fn fold_to_lookup(&self) -> LookupType {
-        self.clone()
-            .into_par_iter()
-            .fold(
-                || BTreeMap::new(),
-                |mut tree, char| {
-                    tree.insert(char, char.score());
-                    tree
-                },
-            )
-            .reduce(
-                || BTreeMap::new(),
-                |mut tree, current| {
-                    for (char, value) in &current {
-                        tree.insert(*char, *value);
+            .map(|&start| {
+                let group = &self[start..self.len().min(start + size)];
+                let line = start + 1;
+                if group.len() < size {
+                    return Err(GroupError::Incomplete {
+                        line,
+                        found: group.len(),
+                        size,
+                    });
+                }
+                let shared = group
+                    .iter()
+                    .map(|line| ItemSet::<WORDS>::with_scheme(line, scheme))
+                    .reduce(ItemSet::intersection)
+                    .unwrap_or_default();
+                match (shared.first(), shared.len()) {
+                    (Some(index), 1) => Ok(Badge {
+                        line,
+                        item: scheme.item(index).unwrap_or('?'),
+                        priority: scheme.weight(index),
+                    }),
+                    (None, _) => Err(GroupError::NoBadge { line }),
+                    _ => Err(GroupError::MultipleBadges {
+                        line,
+                        items: shared.items(scheme),
+                    }),
+                }
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// problem found with a single rucksack line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RucksackIssue {
+    // the compartments can't be the same size
+    OddLength(usize),
+    NoSharedItem,
+    MultipleSharedItems(Vec<char>),
+    InvalidItems(Vec<char>),
+}
+
+impl std::fmt::Display for RucksackIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OddLength(len) => write!(f, "odd length {len}"),
+            Self::NoSharedItem => write!(f, "compartments share no item"),
+            Self::MultipleSharedItems(items) => write!(
+                f,
+                "compartments share {} items: {}",
+                items.len(),
+                items.iter().collect::<String>()
+            ),
+            Self::InvalidItems(items) => {
+                write!(f, "invalid items: {}", items.iter().collect::<String>())
+            }
+        }
+    }
+}
+
+trait RucksackValidator {
+    fn validate(&self, scheme: &dyn PriorityScheme) -> Vec<(usize, RucksackIssue)>;
+    fn validate_with<const WORDS: usize>(
+        &self,
+        scheme: &dyn PriorityScheme,
+    ) -> Vec<(usize, RucksackIssue)>;
+}
+
+impl RucksackValidator for [&str] {
+    // every issue in the input, tagged with its 1 based line number
+    fn validate(&self, scheme: &dyn PriorityScheme) -> Vec<(usize, RucksackIssue)> {
+        with_item_set!(scheme, WORDS => self.validate_with::<WORDS>(scheme))
+    }
+
+    fn validate_with<const WORDS: usize>(
+        &self,
+        scheme: &dyn PriorityScheme,
+    ) -> Vec<(usize, RucksackIssue)> {
+        self.par_iter()
+            .enumerate()
+            .flat_map_iter(|(index, line)| {
+                let mut issues = vec![];
+                let invalid = line
+                    .chars()
+                    .filter(|item| scheme.index(*item).is_none())
+                    .collect::<Vec<_>>();
+                if !invalid.is_empty() {
+                    issues.push(RucksackIssue::InvalidItems(invalid));
+                }
+                let len = line.chars().count();
+                if len % 2 == 1 {
+                    issues.push(RucksackIssue::OddLength(len));
+                } else {
+                    let (first, second) = compartments(line);
+                    let shared = ItemSet::<WORDS>::with_scheme(first, scheme)
+                        .intersection(ItemSet::with_scheme(second, scheme));
+                    if shared.is_empty() {
+                        issues.push(RucksackIssue::NoSharedItem);
+                    } else if shared.len() > 1 {
+                        issues.push(RucksackIssue::MultipleSharedItems(shared.items(scheme)));
                     }
-                    tree
-                },
-            )
-    }
-
-    fn fold_to_duplicate_lookup(&self, target: &LookupType) -> LookupType {
-        self.clone()
-            .into_par_iter()
-            .fold(
-                || BTreeMap::new(),
-                |mut tree, char| {
-                    if target.get(&char).is_some() {
-                        tree.insert(char, char.score());
+                }
+                issues.into_iter().map(move |issue| (index + 1, issue))
+            })
+            .collect()
+    }
+}
+
+/// `--explain` breakdown of one rucksack
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ExplainedRucksack<'a> {
+    line: usize,
+    compartments: (&'a str, &'a str),
+    shared: Vec<char>,
+    priority: u32,
+}
+
+/// `--explain` breakdown of one group, `members` holds each elf's line and the
+/// candidates it ruled out, the first elf's items are the starting candidates
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ExplainedGroup {
+    line: usize,
+    candidates: Vec<char>,
+    members: Vec<(usize, Vec<char>)>,
+    badge: Vec<char>,
+    priority: u32,
+}
+
+trait Explain {
+    fn explain_rucksacks(&self, scheme: &dyn PriorityScheme) -> Vec<ExplainedRucksack<'_>>;
+    fn explain_groups(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Vec<ExplainedGroup>;
+}
+
+impl Explain for [&str] {
+    fn explain_rucksacks(&self, scheme: &dyn PriorityScheme) -> Vec<ExplainedRucksack<'_>> {
+        fn explain<'a, const WORDS: usize>(
+            lines: &[&'a str],
+            scheme: &dyn PriorityScheme,
+        ) -> Vec<ExplainedRucksack<'a>> {
+            lines
+                .iter()
+                .enumerate()
+                .map(|(index, line)| {
+                    let (first, second) = compartments(line);
+                    let shared = ItemSet::<WORDS>::with_scheme(first, scheme)
+                        .intersection(ItemSet::with_scheme(second, scheme));
+                    ExplainedRucksack {
+                        line: index + 1,
+                        compartments: (first, second),
+                        shared: shared.items(scheme),
+                        priority: shared.weight_sum(scheme),
                     }
-                    tree
-                },
-            )
-            .reduce(
-                || BTreeMap::new(),
-                |mut tree, current| {
-                    for (char, value) in &current {
-                        tree.insert(*char, *value);
+                })
+                .collect()
+        }
+        with_item_set!(scheme, WORDS => explain::<WORDS>(self, scheme))
+    }
+
+    fn explain_groups(
+        &self,
+        grouping: Grouping,
+        scheme: &dyn PriorityScheme,
+    ) -> Vec<ExplainedGroup> {
+        fn explain<const WORDS: usize>(
+            lines: &[&str],
+            grouping: Grouping,
+            scheme: &dyn PriorityScheme,
+        ) -> Vec<ExplainedGroup> {
+            grouping
+                .starts(lines.len())
+                .into_iter()
+                .map(|start| {
+                    let group = &lines[start..lines.len().min(start + grouping.size())];
+                    let mut sets = group
+                        .iter()
+                        .map(|line| ItemSet::<WORDS>::with_scheme(line, scheme));
+                    let first = sets.next().unwrap_or_default();
+                    let mut candidates = first;
+                    let members = sets
+                        .enumerate()
+                        .map(|(offset, set)| {
+                            let eliminated = candidates.difference(set);
+                            candidates = candidates.intersection(set);
+                            (start + offset + 2, eliminated.items(scheme))
+                        })
+                        .collect();
+                    ExplainedGroup {
+                        line: start + 1,
+                        candidates: first.items(scheme),
+                        members,
+                        badge: candidates.items(scheme),
+                        priority: candidates.weight_sum(scheme),
                     }
-                    tree
-                },
-            )
+                })
+                .collect()
+        }
+        with_item_set!(scheme, WORDS => explain::<WORDS>(self, grouping, scheme))
     }
 }
 
-/// Lowercase item types `a` through `z` have priorities 1 through 26
-///
-/// Uppercase item types `A` through `Z` have priorities 27 through 52
+fn print_explained(lines: &[&str], grouping: Grouping, scheme: &dyn PriorityScheme) {
+    for rucksack in lines.explain_rucksacks(scheme) {
+        let (first, second) = rucksack.compartments;
+        println!(
+            "line {}: {first} | {second} -> {} ({})",
+            rucksack.line,
+            rucksack.shared.iter().collect::<String>(),
+            rucksack.priority
+        );
+    }
+    for group in lines.explain_groups(grouping, scheme) {
+        println!(
+            "group at line {}: candidates {}",
+            group.line,
+            group.candidates.iter().collect::<String>()
+        );
+        for (line, eliminated) in &group.members {
+            println!(
+                "  line {line} eliminates {}",
+                eliminated.iter().collect::<String>()
+            );
+        }
+        println!(
+            "  badge {} ({})",
+            group.badge.iter().collect::<String>(),
+            group.priority
+        );
+    }
+}
+
+// part 1 under any scheme, items the scheme doesn't know are skipped
+fn compartment_total(lines: &[&str], scheme: &dyn PriorityScheme) -> u32 {
+    fn total<const WORDS: usize>(lines: &[&str], scheme: &dyn PriorityScheme) -> u32 {
+        lines
+            .par_iter()
+            .map(|line| {
+                let (first, second) = compartments(line);
+                ItemSet::<WORDS>::with_scheme(first, scheme)
+                    .intersection(ItemSet::with_scheme(second, scheme))
+                    .weight_sum(scheme)
+            })
+            .sum()
+    }
+    with_item_set!(scheme, WORDS => total::<WORDS>(lines, scheme))
+}
+
 fn part1(lines: &[&str]) -> u32 {
     let start = std::time::Instant::now();
     let answer = lines
         .par_iter()
-        .fold(
-            || 0,
-            |mut count, line| {
-                count += line.split_at(line.len() / 2).find_char_intersection();
-                count
-            },
-        )
+        .map(|line| {
+            let (first, second) = line.split_at(line.len() / 2);
+            // with the default scheme an item's index is its priority
+            ItemSet::from(first)
+                .intersection(ItemSet::from(second))
+                .first()
+                .unwrap_or_default()
+        })
         .sum::<u32>();
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     answer
 }
 
-fn part2(lines: &[&str]) -> u32 {
+fn badge_total(lines: &[&str], grouping: Grouping, scheme: &dyn PriorityScheme) -> u32 {
     let start = std::time::Instant::now();
-    // split input into a vector of chunks of 3
-    let chunks = lines.par_chunks(3).collect::<Vec<_>>();
-    let answer = chunks
-        .par_iter()
-        .fold(
-            || 0,
-            |mut answer, chunk| {
-                // find matches between 3 string inputs
-                let (first, second, third) =
-                    (chunk[0].par_chars(), chunk[1].par_chars(), chunk[2].chars());
-                // set baseline of char
-                let initial_lookup = first.fold_to_lookup();
-                // reduce to only duplicates
-                let potential_solutions = second.fold_to_duplicate_lookup(&initial_lookup);
-                // break to final char that all 3 lines contain
-                for char in third {
-                    if potential_solutions.get(&char).is_some() {
-                        answer += char.score();
-                        // no need to continue
-                        break;
-                    }
-                }
-                answer
-            },
-        )
-        .sum::<u32>();
+    let answer = match lines.find_badges(grouping, scheme) {
+        Ok(badges) => badges.iter().map(|badge| badge.priority).sum(),
+        Err(err) => {
+            tracing::error!("{err}");
+            std::process::exit(1)
+        }
+    };
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     answer
 }
 
-pub fn main() {
+// items of the scheme that no rucksack holds
+fn missing_items(lines: &[&str], scheme: &dyn PriorityScheme) -> Vec<char> {
+    fn missing<const WORDS: usize>(lines: &[&str], scheme: &dyn PriorityScheme) -> Vec<char> {
+        let held = lines
+            .iter()
+            .map(|line| ItemSet::<WORDS>::with_scheme(line, scheme))
+            .fold(ItemSet::default(), ItemSet::union);
+        (1..=scheme.len())
+            .filter(|index| !held.contains(*index))
+            .filter_map(|index| scheme.item(index))
+            .collect()
+    }
+    with_item_set!(scheme, WORDS => missing::<WORDS>(lines, scheme))
+}
+
+// the one item every elf in each group of 3 holds
+fn part2(lines: &[&str]) -> u32 {
+    badge_total(lines, Grouping::Chunks(3), &Letters)
+}
+
+// `--scheme letters|alphanumeric`, `--symbols <chars>` or `--scheme-file <path>`
+fn scheme_from_args() -> Result<Option<Box<dyn PriorityScheme>>> {
+    if let Some(symbols) = flag_value("--symbols") {
+        return Ok(Some(Box::new(SymbolTable::from_symbols(&symbols)?)));
+    }
+    if let Some(path) = flag_value("--scheme-file") {
+        let table = SymbolTable::parse(&std::fs::read_to_string(path)?)?;
+        return Ok(Some(Box::new(table)));
+    }
+    match flag_value("--scheme").as_deref() {
+        None => Ok(None),
+        Some("letters") => Ok(Some(Box::new(Letters))),
+        Some("alphanumeric") => Ok(Some(Box::new(Alphanumeric))),
+        Some(other) => anyhow::bail!("unknown scheme {other}"),
+    }
+}
+
+pub fn main() -> Result<()> {
     init_logger();
     let lines = INPUT.par_lines().collect::<Vec<_>>();
-    let total = part1(&lines);
+    let custom = scheme_from_args()?;
+    let custom_scheme = custom.is_some();
+    let scheme = custom.unwrap_or_else(|| Box::new(Letters));
+    // `--validate` reports every bad rucksack, `--strict` refuses to solve with any
+    let (validate, strict) = (has_flag("--validate"), has_flag("--strict"));
+    if validate || strict {
+        let issues = lines.validate(scheme.as_ref());
+        for (line, issue) in &issues {
+            println!("line {line}: {issue}");
+        }
+        if strict && !issues.is_empty() {
+            anyhow::bail!("{} rucksack issues found", issues.len());
+        }
+        if validate {
+            return Ok(());
+        }
+    }
+    // `--missing` lists the items no rucksack holds
+    if has_flag("--missing") {
+        let missing = missing_items(&lines, scheme.as_ref());
+        if missing.is_empty() {
+            println!("every item is held");
+        } else {
+            println!("{}", missing.iter().collect::<String>());
+        }
+        return Ok(());
+    }
+    // `--group-size N` and `--sliding` change how elves are grouped
+    let size = flag_value("--group-size").map_or(Ok(3), |size| size.parse())?;
+    let grouping = if has_flag("--sliding") {
+        Grouping::Sliding(size)
+    } else {
+        Grouping::Chunks(size)
+    };
+    if has_flag("--explain") {
+        print_explained(&lines, grouping, scheme.as_ref());
+        return Ok(());
+    }
+    let total = if custom_scheme {
+        compartment_total(&lines, scheme.as_ref())
+    } else {
+        part1(&lines)
+    };
     tracing::info!("{total:#?}");
-    let total = part2(&lines);
+    let total = match grouping {
+        Grouping::Chunks(3) if !custom_scheme => part2(&lines),
+        grouping => badge_total(&lines, grouping, scheme.as_ref()),
+    };
     tracing::info!("{total:#?}");
+    Ok(())
 }
 
 #[allow(unused_imports)]
@@ -186,4 +786,231 @@ pub mod tests {
         let total = part2(&lines);
         assert_eq!(total, 2444);
     }
+
+    #[test]
+    fn day3_item_set_tests() {
+        init_logger();
+        let (a, b) = (ItemSet::from("vJrwpWtwJgWr"), ItemSet::from("hcsFMMfFFhFp"));
+        assert_eq!(
+            a.intersection(b).iter().collect::<Vec<_>>(),
+            vec!['p'.score()]
+        );
+        assert_eq!(a.intersection(b).first(), Some(16));
+        assert!(a.union(b).contains('F'.score()));
+        assert!(!a.difference(b).contains('p'.score()));
+        assert_eq!(a.union(b).len(), a.len() + b.len() - 1);
+        assert!(a.difference(a).is_empty());
+        assert_eq!(ItemSet::from("aZ").weight_sum(&Letters), 1 + 52);
+        // only the first of several shared items scores, --validate reports the rest
+        assert_eq!(part1(&["abab"]), 1);
+        let missing = missing_items(&["abc", "xyzA"], &Letters);
+        assert_eq!(missing.len(), 52 - 7);
+        assert!(!missing.contains(&'A') && missing.contains(&'B'));
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        assert_eq!(
+            missing_items(&lines, &Letters).iter().collect::<String>(),
+            "aeikouxyAEIKOUXY"
+        );
+    }
+
+    #[test]
+    fn day3_group_tests() {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let badges = lines.find_badges(Grouping::Chunks(3), &Letters);
+        assert_eq!(badges.map(|b| b.len()), Ok(100));
+        // cut the last group of 3 short
+        assert_eq!(
+            lines[..299].find_badges(Grouping::Chunks(3), &Letters),
+            Err(GroupError::Incomplete {
+                line: 298,
+                found: 2,
+                size: 3
+            })
+        );
+        assert_eq!(
+            ["abCd", "xCyba"].find_badges(Grouping::Chunks(2), &Letters),
+            Err(GroupError::MultipleBadges {
+                line: 1,
+                items: vec!['a', 'b', 'C']
+            })
+        );
+        let badges = ["abc", "cde", "efg"].find_badges(Grouping::Sliding(2), &Letters);
+        assert_eq!(
+            badges.map(|b| b.iter().map(|b| b.item).collect::<String>()),
+            Ok("ce".to_string())
+        );
+        assert_eq!(
+            ["abc", "cde", "efg"].find_badges(Grouping::Chunks(3), &Letters),
+            Err(GroupError::NoBadge { line: 1 })
+        );
+        assert_eq!(
+            ["abc"].find_badges(Grouping::Sliding(0), &Letters),
+            Err(GroupError::ZeroSize)
+        );
+    }
+
+    #[test]
+    fn day3_validation_tests() {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        assert_eq!(lines.validate(&Letters), vec![]);
+        let lines = [
+            "vJrwpWtwJgWrhcsFMMfFFhFp",
+            "abcXd",
+            "abcxyz",
+            "abCdCb",
+            "ab1b2c",
+            "ééaé",
+        ];
+        assert_eq!(
+            lines.validate(&Letters),
+            vec![
+                (2, RucksackIssue::OddLength(5)),
+                (3, RucksackIssue::NoSharedItem),
+                (4, RucksackIssue::MultipleSharedItems(vec!['b', 'C'])),
+                (5, RucksackIssue::InvalidItems(vec!['1', '2'])),
+                (6, RucksackIssue::InvalidItems(vec!['é', 'é', 'é'])),
+                (6, RucksackIssue::NoSharedItem),
+            ]
+        );
+    }
+
+    #[test]
+    fn day3_priority_scheme_tests() -> Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        // every built in scheme keeps today's answers
+        for scheme in [&Letters as &dyn PriorityScheme, &Alphanumeric] {
+            assert_eq!(compartment_total(&lines, scheme), 7817);
+            assert_eq!(badge_total(&lines, Grouping::Chunks(3), scheme), 2444);
+        }
+        let letters = ('a'..='z').chain('A'..='Z').collect::<String>();
+        let table = SymbolTable::from_symbols(&letters)?;
+        assert_eq!(compartment_total(&lines, &table), 7817);
+        assert_eq!(["a1a2", "b113"].validate(&Alphanumeric), vec![]);
+        assert_eq!(compartment_total(&["a1a2", "b113"], &Alphanumeric), 1 + 54);
+        // symbols with their own weights, and more of them than fit in one word
+        let table = SymbolTable::parse("# fruit\n🍎 10\n🍌 20\n🍇\n")?;
+        assert_eq!(compartment_total(&["🍎🍇🍌🍇"], &table), 3);
+        let badges = ["🍎🍌", "🍌🍇", "🍌"].find_badges(Grouping::Chunks(3), &table);
+        assert_eq!(
+            badges,
+            Ok(vec![Badge {
+                line: 1,
+                item: '🍌',
+                priority: 20
+            }])
+        );
+        let wide = SymbolTable::from_symbols(&('一'..='龥').take(300).collect::<String>())?;
+        let (low, high) = (
+            wide.item(1).unwrap_or_default(),
+            wide.item(300).unwrap_or_default(),
+        );
+        assert_eq!(
+            compartment_total(&[&format!("{high}{low}{high}{low}")], &wide),
+            301
+        );
+        assert!(SymbolTable::parse("ab 3").is_err());
+        assert!(SymbolTable::from_symbols("aa").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day3_explain_tests() {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let rucksacks = lines.explain_rucksacks(&Letters);
+        assert_eq!(rucksacks.iter().map(|r| r.priority).sum::<u32>(), 7817);
+        assert!(rucksacks.iter().all(|r| r.shared.len() == 1));
+        let groups = lines.explain_groups(Grouping::Chunks(3), &Letters);
+        assert_eq!(groups.iter().map(|g| g.priority).sum::<u32>(), 2444);
+        let groups = ["abcd", "bcdx", "cdy"].explain_groups(Grouping::Chunks(3), &Letters);
+        assert_eq!(
+            groups,
+            vec![ExplainedGroup {
+                line: 1,
+                candidates: vec!['a', 'b', 'c', 'd'],
+                members: vec![(2, vec!['a']), (3, vec!['b'])],
+                badge: vec!['c', 'd'],
+                priority: 3 + 4,
+            }]
+        );
+        let rucksacks = ["abcb"].explain_rucksacks(&Letters);
+        assert_eq!(rucksacks[0].compartments, ("ab", "cb"));
+        assert_eq!(rucksacks[0].shared, vec!['b']);
+    }
+
+    // the original approach, kept to benchmark against: a scan of the alphabet
+    // for every char and a `BTreeMap` per rucksack
+    mod legacy {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator};
+        use std::collections::BTreeMap;
+
+        const ALPHABET: [char; 52] = [
+            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
+            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H',
+            'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y',
+            'Z',
+        ];
+
+        fn score(char: char) -> u32 {
+            ALPHABET
+                .par_iter()
+                .position_last(|a| *a == char)
+                .map_or(0, |score| score as u32 + 1)
+        }
+
+        pub fn part1(lines: &[&str]) -> u32 {
+            lines
+                .iter()
+                .map(|line| {
+                    let (first, second) = line.split_at(line.len() / 2);
+                    first
+                        .chars()
+                        .find(|char| second.contains(*char))
+                        .map_or(0, score)
+                })
+                .sum()
+        }
+
+        pub fn part2(lines: &[&str]) -> u32 {
+            lines
+                .chunks(3)
+                .map(|chunk| {
+                    let first = chunk[0]
+                        .chars()
+                        .map(|char| (char, score(char)))
+                        .collect::<BTreeMap<_, _>>();
+                    let second = chunk[1]
+                        .chars()
+                        .filter(|char| first.contains_key(char))
+                        .map(|char| (char, score(char)))
+                        .collect::<BTreeMap<_, _>>();
+                    chunk[2]
+                        .chars()
+                        .find(|char| second.contains_key(char))
+                        .map_or(0, score)
+                })
+                .sum()
+        }
+    }
+
+    // LOG_LEVEL=info cargo test --bin day_3 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn day3_benchmark() {
+        init_logger();
+        let input = [INPUT; 200].join("\n");
+        let lines = input.par_lines().collect::<Vec<_>>();
+        let start = std::time::Instant::now();
+        let expected = (legacy::part1(&lines), legacy::part2(&lines));
+        let legacy = start.elapsed();
+        let start = std::time::Instant::now();
+        let answer = (part1(&lines), part2(&lines));
+        let item_set = start.elapsed();
+        assert_eq!(answer, expected);
+        tracing::info!("alphabet + BTreeMap: {legacy:#?}, ItemSet: {item_set:#?}");
+        assert!(item_set < legacy);
+    }
 }
@@ -1,140 +1,651 @@
-use std::ops::Not;
-
-use advent_of_code_2022::utils::init_logger;
-use array_tool::vec::Intersect;
+use advent_of_code_2022::utils::{flag_value, has_flag, init_logger};
 use rayon::{
-    prelude::{IntoParallelRefIterator, ParallelIterator},
+    prelude::{
+        IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator, ParallelSliceMut,
+    },
     str::ParallelString,
 };
+use std::{cmp::Reverse, collections::BinaryHeap, ops::RangeInclusive, str::FromStr};
 
 const INPUT: &str = include_str!("../../inputs/day4.txt");
 
-trait ArrayHelper {
-    fn to_num_vec(&self) -> Vec<u32>;
-}
-
-trait StringHelper {
-    fn input_to_vec(&self) -> Vec<u32>;
-    fn option_to_num(&self) -> u32;
-    fn get_str(&self) -> &str;
-}
-
-impl StringHelper for Option<&String> {
-    fn input_to_vec(&self) -> Vec<u32> {
-        self.map_or_else(
-            || {
-                tracing::error!("no vec option found");
-                std::process::exit(1);
-            },
-            |str| {
-                str.split('-')
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<_>>()
-                    .to_num_vec()
-            },
-        )
-    }
-
-    fn option_to_num(&self) -> u32 {
-        self.map_or_else(
-            || {
-                tracing::error!("no option found");
-                std::process::exit(1);
-            },
-            |val| match val.parse::<u32>() {
-                Ok(int) => int,
-                Err(err) => {
-                    tracing::error!("error parsing int {err}");
-                    std::process::exit(1)
+/// section id, wide enough for any `u64` bound as well as negative ones
+type Section = i128;
+
+/// inclusive range of section ids assigned to one elf
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Interval {
+    start: Section,
+    end: Section,
+}
+
+/// sorted, disjoint intervals, overlapping or touching intervals are merged on the way in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct IntervalSet(Vec<Interval>);
+
+/// how one interval sits against another, one of Allen's 13 relations
+///
+/// sections are whole units, so `2-4` meets `5-6` and `2-4` overlaps `4-6`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Relation {
+    Before,
+    Meets,
+    Overlaps,
+    Starts,
+    During,
+    Finishes,
+    Equals,
+    FinishedBy,
+    Contains,
+    StartedBy,
+    OverlappedBy,
+    MetBy,
+    After,
+}
+
+impl Interval {
+    const fn new(start: Section, end: Section) -> Option<Self> {
+        if start <= end {
+            Some(Self { start, end })
+        } else {
+            None
+        }
+    }
+
+    // number of sections covered
+    const fn len(&self) -> Section {
+        self.end - self.start + 1
+    }
+
+    // every section of `other` is also in this interval
+    const fn contains(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    const fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    // a single interval only exists when the two overlap or touch
+    fn union(&self, other: &Self) -> Option<Self> {
+        let touching = self.overlaps(other)
+            || self.end.checked_add(1) == Some(other.start)
+            || other.end.checked_add(1) == Some(self.start);
+        if touching {
+            Self::new(self.start.min(other.start), self.end.max(other.end))
+        } else {
+            None
+        }
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        Self::new(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    const fn contains_section(&self, section: Section) -> bool {
+        self.start <= section && section <= self.end
+    }
+
+    fn relation(&self, other: &Self) -> Relation {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        if self.end.checked_add(1) == Some(other.start) {
+            return Relation::Meets;
+        }
+        if other.end.checked_add(1) == Some(self.start) {
+            return Relation::MetBy;
+        }
+        if self.end < other.start {
+            return Relation::Before;
+        }
+        if other.end < self.start {
+            return Relation::After;
+        }
+        match (self.start.cmp(&other.start), self.end.cmp(&other.end)) {
+            (Equal, Equal) => Relation::Equals,
+            (Equal, Less) => Relation::Starts,
+            (Equal, Greater) => Relation::StartedBy,
+            (Greater, Equal) => Relation::Finishes,
+            (Less, Equal) => Relation::FinishedBy,
+            (Greater, Less) => Relation::During,
+            (Less, Greater) => Relation::Contains,
+            (Less, Less) => Relation::Overlaps,
+            (Greater, Greater) => Relation::OverlappedBy,
+        }
+    }
+}
+
+impl Relation {
+    const ALL: [Self; 13] = [
+        Self::Before,
+        Self::Meets,
+        Self::Overlaps,
+        Self::Starts,
+        Self::During,
+        Self::Finishes,
+        Self::Equals,
+        Self::FinishedBy,
+        Self::Contains,
+        Self::StartedBy,
+        Self::OverlappedBy,
+        Self::MetBy,
+        Self::After,
+    ];
+    // one elf fully contains the other, part 1
+    const CONTAINMENT: [Self; 7] = [
+        Self::Starts,
+        Self::During,
+        Self::Finishes,
+        Self::Equals,
+        Self::FinishedBy,
+        Self::Contains,
+        Self::StartedBy,
+    ];
+    // the elves share at least one section, part 2
+    const SHARED: [Self; 9] = [
+        Self::Overlaps,
+        Self::Starts,
+        Self::During,
+        Self::Finishes,
+        Self::Equals,
+        Self::FinishedBy,
+        Self::Contains,
+        Self::StartedBy,
+        Self::OverlappedBy,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Before => "before",
+            Self::Meets => "meets",
+            Self::Overlaps => "overlaps",
+            Self::Starts => "starts",
+            Self::During => "during",
+            Self::Finishes => "finishes",
+            Self::Equals => "equals",
+            Self::FinishedBy => "finished-by",
+            Self::Contains => "contains",
+            Self::StartedBy => "started-by",
+            Self::OverlappedBy => "overlapped-by",
+            Self::MetBy => "met-by",
+            Self::After => "after",
+        }
+    }
+}
+
+#[cfg(test)]
+impl Relation {
+    // the relation seen from the other interval
+    const fn inverse(self) -> Self {
+        Self::ALL[Self::ALL.len() - 1 - self as usize]
+    }
+}
+
+impl FromStr for Relation {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|relation| relation.name() == str)
+            .ok_or_else(|| format!("unknown relation {str}"))
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        let mut intervals = iter.into_iter().collect::<Vec<_>>();
+        intervals.sort_unstable();
+        let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            match merged
+                .last_mut()
+                .and_then(|last| last.union(&interval).map(|u| (last, u)))
+            {
+                Some((last, union)) => *last = union,
+                None => merged.push(interval),
+            }
+        }
+        Self(merged)
+    }
+}
+
+impl IntervalSet {
+    // sections in this set but not in `other`
+    fn difference(&self, other: &Self) -> Self {
+        let mut intervals = vec![];
+        let mut b = 0;
+        for interval in &self.0 {
+            let mut start = interval.start;
+            // skip everything that ends before this interval
+            while b < other.0.len() && other.0[b].end < start {
+                b += 1;
+            }
+            let mut next = b;
+            let mut covered = false;
+            while next < other.0.len() && other.0[next].start <= interval.end {
+                let cut = other.0[next];
+                if cut.start > start {
+                    intervals.extend(Interval::new(start, cut.start - 1));
+                }
+                match cut.end.checked_add(1) {
+                    Some(after) if cut.end < interval.end => start = start.max(after),
+                    _ => {
+                        covered = true;
+                        break;
+                    }
                 }
-            },
-        )
+                next += 1;
+            }
+            if !covered {
+                intervals.extend(Interval::new(start, interval.end));
+            }
+        }
+        Self(intervals)
     }
 
-    fn get_str(&self) -> &str {
-        self.map_or_else(
-            || {
-                tracing::error!("no string option found");
-                std::process::exit(1)
-            },
-            |str| str,
-        )
+    // sections covered by at least one interval
+    fn len(&self) -> Section {
+        self.0.iter().map(Interval::len).sum()
+    }
+
+    // sections inside `within` that nothing in the set covers
+    fn gaps(&self, within: Interval) -> Self {
+        Self(vec![within]).difference(self)
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        self.0.iter().chain(&other.0).copied().collect()
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        let (mut a, mut b) = (0, 0);
+        let mut intervals = vec![];
+        while a < self.0.len() && b < other.0.len() {
+            let (left, right) = (self.0[a], other.0[b]);
+            if let Some(shared) = left.intersection(&right) {
+                intervals.push(shared);
+            }
+            // step past whichever interval ends first
+            if left.end < right.end {
+                a += 1;
+            } else {
+                b += 1;
+            }
+        }
+        Self(intervals)
     }
 }
 
-impl ArrayHelper for Vec<String> {
-    fn to_num_vec(&self) -> Vec<u32> {
-        let (a, b) = (self.first().option_to_num(), self.last().option_to_num());
-        let mut temp = vec![];
-        for i in a..=b {
-            temp.push(i);
+trait Assignments {
+    fn container(&self) -> Option<usize>;
+    fn all_overlap(&self) -> bool;
+    fn conflicts(&self) -> Vec<(usize, usize)>;
+}
+
+impl Assignments for [Interval] {
+    // index of an interval that fully contains every other one
+    fn container(&self) -> Option<usize> {
+        let hull = Interval {
+            start: self.iter().map(|i| i.start).min()?,
+            end: self.iter().map(|i| i.end).max()?,
+        };
+        self.iter().position(|interval| interval.contains(&hull))
+    }
+
+    // in one dimension every pair overlapping means they all share a section
+    fn all_overlap(&self) -> bool {
+        self.split_first().map_or(true, |(first, rest)| {
+            rest.iter()
+                .try_fold(*first, |shared, elf| shared.intersection(elf))
+                .is_some()
+        })
+    }
+
+    // indexes of every pair of intervals that overlap
+    fn conflicts(&self) -> Vec<(usize, usize)> {
+        (0..self.len())
+            .flat_map(|a| (a + 1..self.len()).map(move |b| (a, b)))
+            .filter(|(a, b)| self[*a].overlaps(&self[*b]))
+            .collect()
+    }
+}
+
+trait Coverage {
+    fn busiest_section(&self) -> Option<(Section, usize)>;
+}
+
+impl Coverage for [Interval] {
+    // sweep line over every start and end, returns the first section with the
+    // most intervals over it and how many there are
+    fn busiest_section(&self) -> Option<(Section, usize)> {
+        let mut events = self
+            .iter()
+            .flat_map(|interval| [(interval.start, 0), (interval.end, 1)])
+            .collect::<Vec<_>>();
+        // ends are inclusive, so starts at a section count before ends at it
+        events.sort_unstable();
+        let mut depth = 0;
+        let mut busiest: Option<(Section, usize)> = None;
+        for (section, kind) in events {
+            if kind == 0 {
+                depth += 1;
+                match busiest {
+                    Some((_, most)) if most >= depth => (),
+                    _ => busiest = Some((section, depth)),
+                }
+            } else {
+                depth -= 1;
+            }
         }
-        temp
+        busiest
     }
 }
 
+trait Sweep {
+    fn overlap_count(&self) -> usize;
+    fn overlapping_pairs(&self) -> Vec<(usize, usize)>;
+}
+
+impl Sweep for [Interval] {
+    // every pair minus the pairs where one ends before the other starts,
+    // counted with a binary search over the sorted ends
+    fn overlap_count(&self) -> usize {
+        let mut ends = self.iter().map(|i| i.end).collect::<Vec<_>>();
+        ends.par_sort_unstable();
+        let disjoint = self
+            .par_iter()
+            .map(|interval| ends.partition_point(|end| *end < interval.start))
+            .sum::<usize>();
+        self.len() * self.len().saturating_sub(1) / 2 - disjoint
+    }
+
+    // sweep by start keeping the intervals still open, each new interval
+    // overlaps everything open once the ones ending before it are dropped
+    fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        let mut order = (0..self.len()).collect::<Vec<_>>();
+        order.par_sort_unstable_by_key(|index| self[*index].start);
+        let mut open = BinaryHeap::new();
+        let mut pairs = vec![];
+        for index in order {
+            let interval = self[index];
+            while matches!(open.peek(), Some(Reverse((end, _))) if *end < interval.start) {
+                open.pop();
+            }
+            pairs.extend(
+                open.iter()
+                    .map(|Reverse((_, other))| (index.min(*other), index.max(*other))),
+            );
+            open.push(Reverse((interval.end, index)));
+        }
+        pairs.par_sort_unstable();
+        pairs
+    }
+}
+
+/// how an input line is split into intervals, eg `2-4,6-8` or `-5..-2;1..3`
+#[derive(Debug, Clone)]
+struct LineFormat {
+    elves: String,
+    bounds: String,
+}
+
+impl Default for LineFormat {
+    fn default() -> Self {
+        Self {
+            elves: ",".to_string(),
+            bounds: "-".to_string(),
+        }
+    }
+}
+
+impl LineFormat {
+    fn interval(&self, str: &str) -> Result<Interval, String> {
+        // skip a leading sign so `-5--2` splits after `-5`
+        let sign = usize::from(str.starts_with('-'));
+        let (start, end) = str[sign..]
+            .find(self.bounds.as_str())
+            .map(|at| (&str[..sign + at], &str[sign + at + self.bounds.len()..]))
+            .ok_or_else(|| format!("expected start{}end, found {str}", self.bounds))?;
+        let bound = |bound: &str| {
+            bound
+                .trim()
+                .parse::<Section>()
+                .map_err(|err| format!("error parsing int {bound}: {err}"))
+        };
+        Interval::new(bound(start)?, bound(end)?)
+            .ok_or_else(|| format!("{str} ends before it starts"))
+    }
+
+    // every elf on the line, however many there are
+    fn intervals(&self, line: &str) -> Result<Vec<Interval>, String> {
+        line.split(self.elves.as_str())
+            .map(|elf| self.interval(elf.trim()))
+            .collect()
+    }
+
+    // exactly two elves, as the puzzle's relations need
+    fn pair(&self, line: &str) -> Result<(Interval, Interval), String> {
+        match self.intervals(line)?.as_slice() {
+            [first, last] => Ok((*first, *last)),
+            elves => Err(format!(
+                "expected a pair, found {} elves in {line}",
+                elves.len()
+            )),
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    // `2-93`
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        LineFormat::default().interval(str)
+    }
+}
+
+impl From<Interval> for RangeInclusive<Section> {
+    fn from(interval: Interval) -> Self {
+        interval.start..=interval.end
+    }
+}
+
+// how the first elf of each pair relates to the second
+fn relations(lines: &[&str], format: &LineFormat) -> Vec<Relation> {
+    lines
+        .par_iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let (first, last) = format.pair(line).unwrap_or_else(|err| {
+                tracing::error!("line {}: {err}", index + 1);
+                std::process::exit(1)
+            });
+            first.relation(&last)
+        })
+        .collect()
+}
+
+// pairs whose relation is any of `class`
+fn count_class(lines: &[&str], class: &[Relation]) -> u32 {
+    relations(lines, &LineFormat::default())
+        .into_iter()
+        .filter(|relation| class.contains(relation))
+        .count() as u32
+}
+
 fn part1(lines: &[&str]) -> u32 {
     let start = std::time::Instant::now();
-    let total = lines
-        .par_iter()
-        .fold(
-            || 0,
-            |mut acc, line| {
-                let elves = line
-                    .split(',')
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<_>>();
-                let (first, last) = (elves.first(), elves.last());
-                let first = first
-                    .get_str()
-                    .split('-')
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<_>>();
-                let last = last
-                    .get_str()
-                    .split('-')
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<_>>();
-                let (a, b) = (first.first().option_to_num(), first.last().option_to_num());
-                let (x, y) = (last.first().option_to_num(), last.last().option_to_num());
-                if a <= x && b >= y || x <= a && y >= b {
-                    acc += 1;
-                }
-                acc
-            },
-        )
-        .sum::<u32>();
+    let total = count_class(lines, &Relation::CONTAINMENT);
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     total
 }
 
 fn part2(lines: &[&str]) -> u32 {
     let start = std::time::Instant::now();
-    let total = lines
-        .par_iter()
-        .fold(
-            || 0,
-            |mut acc, line| {
-                let elves = line
-                    .split(',')
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<_>>();
-                let first_sect = elves.first().input_to_vec();
-                let last_sect = elves.last().input_to_vec();
-                if first_sect.intersect(last_sect).is_empty().not() {
-                    acc += 1;
-                }
-                acc
-            },
-        )
-        .sum::<u32>();
+    let total = count_class(lines, &Relation::SHARED);
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     total
 }
 
+// every elf's interval with the line it came from, however many elves a line has
+fn tagged_intervals(lines: &[&str], format: &LineFormat) -> (Vec<usize>, Vec<Interval>) {
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let elves = format.intervals(line).unwrap_or_else(|err| {
+                tracing::error!("line {}: {err}", index + 1);
+                std::process::exit(1)
+            });
+            elves.into_iter().map(move |elf| (index + 1, elf))
+        })
+        .unzip()
+}
+
+// everything each elf position covers, the first elf of every line, then the second...
+fn position_sets(lines: &[&str], format: &LineFormat) -> Vec<IntervalSet> {
+    let mut positions: Vec<Vec<Interval>> = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        let elves = format.intervals(line).unwrap_or_else(|err| {
+            tracing::error!("line {}: {err}", index + 1);
+            std::process::exit(1)
+        });
+        for (position, elf) in elves.into_iter().enumerate() {
+            if position == positions.len() {
+                positions.push(vec![]);
+            }
+            positions[position].push(elf);
+        }
+    }
+    positions.into_iter().map(IntervalSet::from_iter).collect()
+}
+
 pub fn main() {
     init_logger();
     let lines = INPUT.par_lines().collect::<Vec<_>>();
+    // report modes over every elf at once
+    // `--elf-separator` and `--range-separator` change how a line is split
+    let format = LineFormat {
+        elves: flag_value("--elf-separator").unwrap_or_else(|| ",".to_string()),
+        bounds: flag_value("--range-separator").unwrap_or_else(|| "-".to_string()),
+    };
+    if has_flag("--groups") {
+        let (mut contained, mut overlapping) = (0, 0);
+        for (index, line) in lines.iter().enumerate() {
+            let elves = format.intervals(line).unwrap_or_else(|err| {
+                tracing::error!("line {}: {err}", index + 1);
+                std::process::exit(1)
+            });
+            contained += usize::from(elves.container().is_some());
+            overlapping += usize::from(elves.all_overlap());
+            let conflicts = elves.conflicts();
+            if !conflicts.is_empty() {
+                let pairs = conflicts
+                    .iter()
+                    .map(|(a, b)| format!("{}&{}", a + 1, b + 1))
+                    .collect::<Vec<_>>();
+                println!("line {}: elves {} conflict", index + 1, pairs.join(", "));
+            }
+        }
+        println!("lines where one elf contains the rest: {contained}");
+        println!("lines where every elf overlaps: {overlapping}");
+        return;
+    }
+    // `--sweep` finds overlaps between any two elves in the file, `--pairs` lists them
+    if has_flag("--sweep") {
+        let start = std::time::Instant::now();
+        let (line_of, intervals) = tagged_intervals(&lines, &format);
+        println!("overlapping pairs: {}", intervals.overlap_count());
+        if has_flag("--pairs") {
+            for (a, b) in intervals.overlapping_pairs() {
+                println!("line {} & line {}", line_of[a], line_of[b]);
+            }
+        }
+        tracing::info!("operation complete in: {:#?}", start.elapsed());
+        return;
+    }
+    if has_flag("--relations") {
+        let relations = relations(&lines, &format);
+        for relation in Relation::ALL {
+            let count = relations.iter().filter(|r| **r == relation).count();
+            println!("{:>13}: {count}", relation.name());
+        }
+        return;
+    }
+    // `--relation during` prints every line whose pair is in that relation
+    if let Some(relation) = flag_value("--relation") {
+        let relation = relation.parse::<Relation>().unwrap_or_else(|err| {
+            tracing::error!("{err}");
+            std::process::exit(1)
+        });
+        for (line, found) in lines.iter().zip(relations(&lines, &format)) {
+            if found == relation {
+                println!("{line}");
+            }
+        }
+        return;
+    }
+    if has_flag("--coverage") {
+        let covered = tagged_intervals(&lines, &format)
+            .1
+            .into_iter()
+            .collect::<IntervalSet>();
+        println!("sections covered: {}", covered.len());
+        return;
+    }
+    // `--shared` compares what the first elves cover against the second and so on
+    if has_flag("--shared") {
+        let positions = position_sets(&lines, &format);
+        let any = positions
+            .iter()
+            .fold(IntervalSet::default(), |any, set| any.union(set));
+        let every = positions.iter().skip(1).fold(
+            positions.first().cloned().unwrap_or_default(),
+            |every, set| every.intersection(set),
+        );
+        println!("sections covered by any elf position: {}", any.len());
+        println!("sections covered by every elf position: {}", every.len());
+        return;
+    }
+    if let Some(within) = flag_value("--gaps") {
+        let covered = tagged_intervals(&lines, &format)
+            .1
+            .into_iter()
+            .collect::<IntervalSet>();
+        let within = format.interval(&within).unwrap_or_else(|err| {
+            tracing::error!("--gaps: {err}");
+            std::process::exit(1)
+        });
+        for gap in covered.gaps(within).0 {
+            println!("{}-{}", gap.start, gap.end);
+        }
+        return;
+    }
+    // `--section N` prints every line with an elf assigned that section
+    if let Some(section) = flag_value("--section") {
+        let section = section.parse::<Section>().unwrap_or_else(|err| {
+            tracing::error!("error parsing int {section}: {err}");
+            std::process::exit(1)
+        });
+        let (line_of, intervals) = tagged_intervals(&lines, &format);
+        let mut found = line_of
+            .iter()
+            .zip(&intervals)
+            .filter(|(_, interval)| interval.contains_section(section))
+            .map(|(line, _)| *line)
+            .collect::<Vec<_>>();
+        found.dedup();
+        for line in found {
+            println!("{}", lines[line - 1]);
+        }
+        return;
+    }
+    if has_flag("--busiest") {
+        if let Some((section, elves)) = tagged_intervals(&lines, &format).1.busiest_section() {
+            println!("{elves} elves are assigned section {section}");
+        }
+        return;
+    }
     let answer = part1(&lines);
     tracing::info!("{answer:#?}");
     let answer2 = part2(&lines);
@@ -146,6 +657,20 @@ pub fn main() {
 pub mod tests {
 
     use super::*;
+    use advent_of_code_2022::utils::Xorshift;
+
+    trait StringHelper {
+        fn to_interval(&self) -> Interval;
+    }
+
+    impl StringHelper for str {
+        fn to_interval(&self) -> Interval {
+            self.parse().unwrap_or_else(|err| {
+                tracing::error!("{err}");
+                std::process::exit(1)
+            })
+        }
+    }
 
     #[test]
     fn day4_tests() {
@@ -156,4 +681,207 @@ pub mod tests {
         let total = part2(&lines);
         assert_eq!(total, 827);
     }
+
+    #[test]
+    fn day4_interval_tests() -> Result<(), String> {
+        init_logger();
+        // far too wide to expand into a vec of sections
+        let lines = ["1-4000000000,2-3", "1-4000000000,3999999999-4000000001"];
+        assert_eq!(part1(&lines), 1);
+        assert_eq!(part2(&lines), 2);
+        let (a, b) = LineFormat::default().pair("2-6,4-8")?;
+        assert_eq!(a.intersection(&b), Interval::new(4, 6));
+        assert_eq!(a.union(&b), Interval::new(2, 8));
+        assert_eq!(
+            "2-3".to_interval().union(&"4-5".to_interval()),
+            Interval::new(2, 5)
+        );
+        assert_eq!("2-3".to_interval().union(&"5-6".to_interval()), None);
+        assert_eq!("2-3".to_interval().intersection(&"4-5".to_interval()), None);
+        assert!(a.contains_section(6) && !a.contains_section(7));
+        assert_eq!(RangeInclusive::from(a).count() as Section, a.len());
+        assert!("5-2".parse::<Interval>().is_err());
+        assert!("5".parse::<Interval>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day4_relation_tests() -> Result<(), String> {
+        init_logger();
+        let format = LineFormat::default();
+        let examples = [
+            ("1-2,4-5", Relation::Before),
+            ("1-3,4-5", Relation::Meets),
+            ("1-4,4-5", Relation::Overlaps),
+            ("4-4,4-5", Relation::Starts),
+            ("2-3,1-5", Relation::During),
+            ("3-5,1-5", Relation::Finishes),
+            ("1-5,1-5", Relation::Equals),
+            ("1-5,3-5", Relation::FinishedBy),
+            ("1-5,2-3", Relation::Contains),
+            ("1-5,1-1", Relation::StartedBy),
+            ("3-6,1-4", Relation::OverlappedBy),
+            ("4-5,1-3", Relation::MetBy),
+            ("4-5,1-2", Relation::After),
+        ];
+        for (line, expected) in examples {
+            let (first, last) = format.pair(line)?;
+            assert_eq!(first.relation(&last), expected, "{line}");
+            assert_eq!(last.relation(&first), expected.inverse(), "{line}");
+            assert_eq!(expected.name().parse::<Relation>(), Ok(expected));
+        }
+        // exactly one relation holds, so the classes agree with the interval helpers
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        for line in &lines {
+            let (first, last) = format.pair(line)?;
+            let found = first.relation(&last);
+            assert_eq!(
+                Relation::CONTAINMENT.contains(&found),
+                first.contains(&last) || last.contains(&first)
+            );
+            assert_eq!(Relation::SHARED.contains(&found), first.overlaps(&last));
+        }
+        assert!("sideways".parse::<Relation>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day4_sweep_tests() {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (line_of, intervals) = tagged_intervals(&lines, &LineFormat::default());
+        assert_eq!(line_of.len(), lines.len() * 2);
+        // quadratic check over the real input
+        let pairs = intervals.overlapping_pairs();
+        assert_eq!(pairs, intervals.conflicts());
+        assert_eq!(intervals.overlap_count(), pairs.len());
+        // the per line pairs are a subset of the whole file
+        let same_line = pairs
+            .iter()
+            .filter(|(a, b)| line_of[*a] == line_of[*b])
+            .count();
+        assert_eq!(same_line, 827);
+        // a million short ranges, far past what the pairwise check could handle
+        let mut rng = Xorshift::new(0x2545_f491_4f6c_dd1d);
+        let many = (0..1_000_000)
+            .map(|_| {
+                let seed = rng.next_u64();
+                let start = Section::from(seed % 100_000_000);
+                Interval {
+                    start,
+                    end: start + Section::from(seed % 500),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(many.overlap_count(), many.overlapping_pairs().len());
+        assert_eq!(Vec::<Interval>::new().overlap_count(), 0);
+    }
+
+    #[test]
+    fn day4_line_format_tests() -> Result<(), String> {
+        init_logger();
+        let format = LineFormat::default();
+        let elves = format.intervals("-5--2,-3-4,0-1")?;
+        assert_eq!(
+            elves,
+            vec![
+                Interval { start: -5, end: -2 },
+                Interval { start: -3, end: 4 },
+                Interval { start: 0, end: 1 },
+            ]
+        );
+        assert_eq!(elves.container(), None);
+        assert!(!elves.all_overlap());
+        assert_eq!(elves.conflicts(), vec![(0, 1), (1, 2)]);
+        let format = LineFormat {
+            elves: "; ".to_string(),
+            bounds: "..".to_string(),
+        };
+        let elves = format.intervals("1..10; 2..3; 3..9")?;
+        assert_eq!(elves.len(), 3);
+        assert_eq!(elves.container(), Some(0));
+        assert!(elves.all_overlap());
+        assert_eq!(elves.conflicts().len(), 3);
+        assert!(format.intervals("1..10; 2-3").is_err());
+        assert!(format.intervals("3..1").is_err());
+        assert_eq!(
+            format.pair("1..2; 3..4")?,
+            (Interval { start: 1, end: 2 }, Interval { start: 3, end: 4 })
+        );
+        assert!(format.pair("1..2; 3..4; 5..6").is_err());
+        let (_, intervals) = tagged_intervals(&["1..4; 3..9", "8..12; 20..20"], &format);
+        let covered = intervals.into_iter().collect::<IntervalSet>();
+        assert_eq!(covered.len(), 13);
+        // the puzzle answers are the two elf case of the new queries
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let pairs = lines
+            .iter()
+            .map(|line| LineFormat::default().intervals(line))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            pairs.iter().filter(|p| p.container().is_some()).count(),
+            503
+        );
+        assert_eq!(pairs.iter().filter(|p| p.all_overlap()).count(), 827);
+        Ok(())
+    }
+
+    #[test]
+    fn day4_interval_set_tests() {
+        init_logger();
+        let set = |intervals: &str| -> IntervalSet {
+            intervals
+                .split(',')
+                .map(StringHelper::to_interval)
+                .collect()
+        };
+        assert_eq!(set("5-7,1-2,3-3,9-10"), set("1-3,5-7,9-10"));
+        assert_eq!(set("1-3,5-7").len(), 6);
+        assert_eq!(set("1-5,8-12").union(&set("4-9,20-20")), set("1-12,20-20"));
+        assert_eq!(
+            set("1-5,8-12,15-20").intersection(&set("4-9,11-16")),
+            set("4-5,8-9,11-12,15-16")
+        );
+        assert_eq!(
+            set("1-10,15-20").difference(&set("3-4,8-16")),
+            set("1-2,5-7,17-20")
+        );
+        assert_eq!(set("1-10").difference(&set("1-10")), IntervalSet::default());
+        let max = format!("0-{}", u64::MAX);
+        assert_eq!(
+            set(&max).difference(&set(&format!("5-{}", u64::MAX))),
+            set("0-4")
+        );
+        assert_eq!(
+            set("2-4,8-9").gaps("1-10".to_interval()),
+            set("1-1,5-7,10-10")
+        );
+        let intervals = "1-5,4-8,5-6,9-9".split(',').map(StringHelper::to_interval);
+        assert_eq!(
+            intervals.collect::<Vec<_>>().busiest_section(),
+            Some((5, 3))
+        );
+        // the real input, checked against a brute force count over its 1-99 sections
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let intervals = tagged_intervals(&lines, &LineFormat::default()).1;
+        let (section, elves) = intervals.busiest_section().unwrap_or_default();
+        let count = |section: Section| {
+            intervals
+                .iter()
+                .filter(|i| i.contains_section(section))
+                .count()
+        };
+        assert_eq!(count(section), elves);
+        assert!((0..=100).all(|section| count(section) <= elves));
+        let covered = intervals.iter().copied().collect::<IntervalSet>();
+        assert_eq!(
+            covered.len(),
+            (0..=100).filter(|s| count(*s) > 0).count() as Section
+        );
+        // one set per elf position, a line with a third elf adds a third set
+        assert_eq!(
+            position_sets(&["2-4,6-8", "5-5,7-9,1-1"], &LineFormat::default()),
+            vec![set("2-5"), set("6-9"), set("1-1")]
+        );
+    }
 }
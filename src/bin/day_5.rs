@@ -1,169 +1,1118 @@
-use advent_of_code_2022::{
-    types::{StringHelpers, VecHelpers},
-    utils::init_logger,
-};
-use rayon::{
-    prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
-    str::ParallelString,
-};
-use std::collections::BTreeMap;
+use advent_of_code_2022::utils::{flag_value, has_flag, init_logger};
+use rayon::{prelude::ParallelIterator, str::ParallelString};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
 
 const INPUT: &str = include_str!("../../inputs/day5.txt");
 
 trait CrateTree {
-    fn generate_tree(&self) -> (BTreeMap<u32, Vec<String>>, usize);
-    fn generate_instructions(&self, skip: usize) -> Vec<Command>;
+    fn generate_tree(&self) -> anyhow::Result<(BTreeMap<u32, Vec<String>>, usize)>;
+    fn run_instructions(
+        &self,
+        skip: usize,
+        crates: &mut Stacks,
+        crane: &dyn Crane,
+        done: usize,
+    ) -> Result<Vec<Command>, MoveError>;
+}
+
+/// how the crates on top of each stack are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TopFormat {
+    // the puzzle answer, `CMZ`, empty stacks add nothing
+    Concatenated,
+    // `C,M,Z`, an empty stack leaves an empty field
+    Comma,
+    // `{"1":"C","2":"M","3":null}`, keyed by stack number
+    Json,
+}
+
+impl FromStr for TopFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "concat" | "concatenated" => Ok(Self::Concatenated),
+            "comma" => Ok(Self::Comma),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown top format {str}, expected concat, comma or json"
+            )),
+        }
+    }
 }
 
 trait CrateTreeHelper {
     fn aggregate_top_letters(&self) -> String;
-    fn get_safe(&self, index: u32) -> Vec<String>;
+    fn format_tops(&self, format: TopFormat) -> String;
+    fn stacks_mut(&mut self, target: u32, destination: u32) -> (&mut Vec<Crate>, &mut Vec<Crate>);
+}
+
+/// one id per physical crate, its label lives in `Stacks::labels`
+type Crate = u32;
+
+/// the stacks as plain vecs of crate ids, so a move shifts ids in place
+/// rather than cloning stacks of strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stacks {
+    numbers: Vec<u32>,
+    stacks: Vec<Vec<Crate>>,
+    labels: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Command {
     pub amount: u32,
     pub target: u32,
     pub destination: u32,
+    // line of the input the move was read from
+    pub line: usize,
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.amount, self.target, self.destination
+        )
+    }
+}
+
+/// why a move can't be run, with the stacks as they stood just before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MoveError {
+    // anything other than `move N from A to B`
+    Malformed {
+        line: usize,
+        instruction: usize,
+        text: String,
+        stacks: BTreeMap<u32, Vec<String>>,
+    },
+    UnknownStack {
+        line: usize,
+        instruction: usize,
+        stack: u32,
+        stacks: BTreeMap<u32, Vec<String>>,
+    },
+    NotEnoughCrates {
+        line: usize,
+        instruction: usize,
+        stack: u32,
+        amount: u32,
+        held: usize,
+        stacks: BTreeMap<u32, Vec<String>>,
+    },
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stacks = match self {
+            Self::Malformed {
+                line,
+                instruction,
+                text,
+                stacks,
+            } => {
+                write!(
+                    f,
+                    "line {line}: instruction {instruction} expected `move N from A to B`, found `{text}`"
+                )?;
+                stacks
+            }
+            Self::UnknownStack {
+                line,
+                instruction,
+                stack,
+                stacks,
+            } => {
+                write!(
+                    f,
+                    "line {line}: instruction {instruction} references stack {stack} which doesn't exist"
+                )?;
+                stacks
+            }
+            Self::NotEnoughCrates {
+                line,
+                instruction,
+                stack,
+                amount,
+                held,
+                stacks,
+            } => {
+                write!(
+                    f,
+                    "line {line}: instruction {instruction} moves {amount} crates but stack {stack} holds only {held}"
+                )?;
+                stacks
+            }
+        };
+        for (number, stack) in stacks {
+            write!(f, "\n{number}: {}", stack.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// every `[label]` on a row with the columns it covers, labels can be any
+// length and hold anything but a closing bracket
+fn crate_labels(row: &str, line: usize) -> anyhow::Result<Vec<(usize, usize, &str)>> {
+    let mut labels = vec![];
+    let mut open = None;
+    for (column, (index, char)) in row.char_indices().enumerate() {
+        match (open, char) {
+            (None, '[') => open = Some((column, index)),
+            (Some((start, from)), ']') => {
+                labels.push((start, column + 1, &row[from + 1..index]));
+                open = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some((column, _)) = open {
+        anyhow::bail!("line {line}: crate opened at column {column} is never closed in {row}");
+    }
+    Ok(labels)
+}
+
+// `1 [N] [R] [G]`, one stack per line with its number first, a bare number
+// is an empty stack
+fn parse_listing(header: &[&str]) -> anyhow::Result<BTreeMap<u32, Vec<String>>> {
+    let mut crates = BTreeMap::new();
+    for (index, line) in header.iter().enumerate() {
+        let number = line.split('[').next().unwrap_or_default().trim();
+        let crate_num = number.parse::<u32>().map_err(|err| {
+            anyhow::anyhow!(
+                "line {}: error parsing stack number {number}: {err}",
+                index + 1
+            )
+        })?;
+        crates.insert(
+            crate_num,
+            crate_labels(line, index + 1)?
+                .into_iter()
+                .map(|(_, _, label)| label.to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+    Ok(crates)
+}
+
+// the puzzle's own drawing, crates stacked upwards over a footer of stack numbers
+//
+//     [D]
+// [N] [C]
+// [Z] [M] [P]
+//  1   2   3
+fn parse_drawing(header: &[&str]) -> anyhow::Result<BTreeMap<u32, Vec<String>>> {
+    let (footer, rows) = header.split_last().map_or((&"", header), |(f, r)| (f, r));
+    // twice the centre column of every stack number, so stacks past 9 still line up
+    let columns = spans(footer, ' ')
+        .into_iter()
+        .map(|(start, end)| {
+            let num = &footer[start..end];
+            num.parse::<u32>()
+                .map(|num| (start + end, num))
+                .map_err(|err| {
+                    anyhow::anyhow!(
+                        "line {}: error parsing stack number {num}: {err}",
+                        header.len()
+                    )
+                })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut crates = columns
+        .iter()
+        .map(|(_, num)| (*num, vec![]))
+        .collect::<BTreeMap<_, Vec<String>>>();
+    for (index, row) in rows.iter().enumerate().rev() {
+        for (start, end, label) in crate_labels(row, index + 1)? {
+            // a crate belongs to whichever stack number sits closest beneath it
+            let nearest = columns
+                .iter()
+                .min_by_key(|(centre, _)| centre.abs_diff(start + end));
+            match nearest {
+                Some((_, num)) => crates.entry(*num).or_default().push(label.to_string()),
+                None => anyhow::bail!(
+                    "line {}: crate {label} has no stack number beneath it",
+                    index + 1
+                ),
+            }
+        }
+    }
+    Ok(crates)
+}
+
+// byte ranges of every run of non `gap` characters
+fn spans(line: &str, gap: char) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = None;
+    for (index, char) in line.char_indices().chain([(line.len(), gap)]) {
+        match (start, char == gap) {
+            (None, false) => start = Some(index),
+            (Some(from), true) => {
+                spans.push((from, index));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    spans
+}
+
+// the drawing ends in a footer that is nothing but stack numbers, while every
+// listing row starts with one, so a listing ending in an empty stack isn't a footer
+fn is_drawing(header: &[&str]) -> bool {
+    match header.split_last() {
+        Some((footer, rows)) => {
+            !footer.trim().is_empty()
+                && footer
+                    .split_whitespace()
+                    .all(|num| num.parse::<u32>().is_ok())
+                && !rows
+                    .iter()
+                    .any(|row| row.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+        }
+        None => false,
+    }
+}
+
+// `move 2 from 4 to 6`
+fn parse_command(text: &str, line: usize) -> Option<Command> {
+    match text.split_whitespace().collect::<Vec<_>>()[..] {
+        ["move", amount, "from", target, "to", destination] => Some(Command {
+            amount: amount.parse().ok()?,
+            target: target.parse().ok()?,
+            destination: destination.parse().ok()?,
+            line,
+        }),
+        _ => None,
+    }
 }
 
 impl CrateTree for [&str] {
-    fn generate_tree(&self) -> (BTreeMap<u32, Vec<String>>, usize) {
-        let mut skip_lines = 0;
-        let mut crates = BTreeMap::new();
-        for line in self {
-            skip_lines += 1;
-            if line.is_empty() {
-                break;
-            }
-            let mut chars = line
-                .split(' ')
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>();
-            let crate_num = chars.get_first().parse_safe::<u32>();
-            chars.remove(0);
-            crates.insert(
-                crate_num,
-                chars
+    // takes either the puzzle's drawing or the `1 [N] [R]` listing
+    fn generate_tree(&self) -> anyhow::Result<(BTreeMap<u32, Vec<String>>, usize)> {
+        let header_len = self
+            .iter()
+            .position(|line| line.is_empty())
+            .unwrap_or(self.len());
+        let header = &self[..header_len];
+        let crates = if is_drawing(header) {
+            parse_drawing(header)?
+        } else {
+            parse_listing(header)?
+        };
+        // instructions start after the blank line
+        Ok((crates, (header_len + 1).min(self.len())))
+    }
+
+    // parses each move only once the ones before it have run on `crates`, so a
+    // bad line is reported against the stacks as they stood when it was reached.
+    // `done` is how many moves `crates` has already had made
+    fn run_instructions(
+        &self,
+        skip: usize,
+        crates: &mut Stacks,
+        crane: &dyn Crane,
+        done: usize,
+    ) -> Result<Vec<Command>, MoveError> {
+        let mut instructions = vec![];
+        for (index, text) in self.iter().enumerate().skip(skip) {
+            if text.trim().is_empty() {
+                continue;
+            }
+            let instruction = done + instructions.len() + 1;
+            let command = parse_command(text, index + 1).ok_or_else(|| MoveError::Malformed {
+                line: index + 1,
+                instruction,
+                text: text.to_string(),
+                stacks: BTreeMap::from(&*crates),
+            })?;
+            try_move(crates, &command, instruction, crane)?;
+            instructions.push(command);
+        }
+        Ok(instructions)
+    }
+}
+
+impl From<&BTreeMap<u32, Vec<String>>> for Stacks {
+    fn from(crates: &BTreeMap<u32, Vec<String>>) -> Self {
+        let mut labels = vec![];
+        let stacks = crates
+            .values()
+            .map(|stack| {
+                stack
                     .iter()
-                    .map(|a| a.replace(['[', ']'], ""))
-                    .collect::<Vec<_>>(),
-            );
+                    .map(|label| {
+                        labels.push(label.clone());
+                        (labels.len() - 1) as Crate
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            numbers: crates.keys().copied().collect(),
+            stacks,
+            labels,
         }
-        (crates, skip_lines)
-    }
-
-    fn generate_instructions(&self, skip: usize) -> Vec<Command> {
-        self.par_iter()
-            .skip(skip)
-            .map(|line| {
-                let parts = line.split_whitespace().fold(vec![], |mut acc, str| {
-                    str.parse::<u32>().map_or((), |int| acc.push(int));
-                    acc
-                });
-                let (amount, target, destination) = (parts[0], parts[1], parts[2]);
-                Command {
-                    amount,
-                    target,
-                    destination,
-                }
+    }
+}
+
+impl From<&Stacks> for BTreeMap<u32, Vec<String>> {
+    fn from(stacks: &Stacks) -> Self {
+        stacks
+            .numbers
+            .iter()
+            .zip(&stacks.stacks)
+            .map(|(number, stack)| (*number, stacks.labels(stack)))
+            .collect()
+    }
+}
+
+impl Stacks {
+    // stacks are nearly always numbered 1 to n, so try that before searching
+    fn index(&self, number: u32) -> Option<usize> {
+        let guess = number.checked_sub(*self.numbers.first()?)? as usize;
+        if self.numbers.get(guess) == Some(&number) {
+            Some(guess)
+        } else {
+            self.numbers.binary_search(&number).ok()
+        }
+    }
+
+    fn stack(&self, number: u32) -> Option<&Vec<Crate>> {
+        self.index(number).map(|index| &self.stacks[index])
+    }
+
+    // label of the top crate of stack `number`, `None` when it's empty
+    fn top(&self, number: u32) -> Option<&str> {
+        let id = self.stack(number)?.last()?;
+        Some(self.labels[*id as usize].as_str())
+    }
+
+    fn index_safe(&self, number: u32) -> usize {
+        self.index(number).unwrap_or_else(|| {
+            tracing::error!("no create elem found at index {number}");
+            std::process::exit(1)
+        })
+    }
+
+    fn labels(&self, stack: &[Crate]) -> Vec<String> {
+        stack
+            .iter()
+            .map(|id| self.labels[*id as usize].clone())
+            .collect()
+    }
+}
+
+const SOURCE_COLOUR: &str = "\x1b[33m";
+const DESTINATION_COLOUR: &str = "\x1b[32m";
+const RESET_COLOUR: &str = "\x1b[0m";
+
+impl Stacks {
+    // the stacks drawn the way the puzzle does, with `moved` highlighting the
+    // source stack and the crates it just put on the destination
+    fn render(&self, moved: Option<&Command>) -> String {
+        let width = self
+            .labels
+            .iter()
+            .map(|label| label.chars().count() + 2)
+            .chain(self.numbers.iter().map(|number| number.to_string().len()))
+            .max()
+            .unwrap_or(3);
+        let colour = |stack: usize| {
+            let number = self.numbers[stack];
+            match moved {
+                Some(command) if command.target == number => Some(SOURCE_COLOUR),
+                Some(command) if command.destination == number => Some(DESTINATION_COLOUR),
+                _ => None,
+            }
+        };
+        let paint = |cell: String, colour: Option<&str>| {
+            colour.map_or(cell.clone(), |colour| {
+                format!("{colour}{cell}{RESET_COLOUR}")
+            })
+        };
+        let height = self.stacks.iter().map(Vec::len).max().unwrap_or_default();
+        let mut rows = (0..height)
+            .rev()
+            .map(|level| {
+                let row = self
+                    .stacks
+                    .iter()
+                    .enumerate()
+                    .map(|(stack, crates)| match crates.get(level) {
+                        Some(id) => {
+                            let cell =
+                                format!("{:^width$}", format!("[{}]", self.labels[*id as usize]));
+                            // only the crates that just landed are highlighted on the destination
+                            let landed = match moved {
+                                Some(command) => {
+                                    command.destination != self.numbers[stack]
+                                        || level + command.amount as usize >= crates.len()
+                                }
+                                None => false,
+                            };
+                            paint(cell, colour(stack).filter(|_| landed))
+                        }
+                        None => " ".repeat(width),
+                    })
+                    .collect::<Vec<_>>();
+                row.join(" ").trim_end().to_string()
             })
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        let footer = (0..self.stacks.len())
+            .map(|stack| paint(format!("{:^width$}", self.numbers[stack]), colour(stack)))
+            .collect::<Vec<_>>();
+        rows.push(footer.join(" "));
+        rows.join("\n")
     }
 }
 
-impl CrateTreeHelper for BTreeMap<u32, Vec<String>> {
+// `50ms`, `1s` or a bare number of milliseconds
+fn parse_delay(delay: &str) -> Result<Duration, String> {
+    let parse = |num: &str| {
+        num.parse::<u64>()
+            .map_err(|err| format!("error parsing delay {delay}: {err}"))
+    };
+    match (delay.strip_suffix("ms"), delay.strip_suffix('s')) {
+        (Some(millis), _) => parse(millis).map(Duration::from_millis),
+        (None, Some(secs)) => parse(secs).map(Duration::from_secs),
+        (None, None) => parse(delay).map(Duration::from_millis),
+    }
+}
+
+// `--animate [--delay 50ms]`, `--step` or `--at N`, all drawing the stacks
+fn show(lines: &[&str], crane: &dyn Crane) -> anyhow::Result<()> {
+    let (crates, skip) = lines.generate_tree()?;
+    let mut crates = Stacks::from(&crates);
+    let instructions = lines.run_instructions(skip, &mut crates.clone(), crane, 0)?;
+    if let Some(at) = flag_value("--at") {
+        let at = at.parse::<usize>()?.min(instructions.len());
+        try_execute(&mut crates, &instructions[..at], crane)?;
+        println!(
+            "{}",
+            crates.render(at.checked_sub(1).map(|last| &instructions[last]))
+        );
+        return Ok(());
+    }
+    let delay = parse_delay(&flag_value("--delay").unwrap_or_else(|| "50ms".to_string()))
+        .map_err(anyhow::Error::msg)?;
+    let step = has_flag("--step");
+    println!("{}", crates.render(None));
+    for (index, command) in instructions.iter().enumerate() {
+        if step {
+            std::io::stdin().read_line(&mut String::new())?;
+        } else {
+            std::thread::sleep(delay);
+        }
+        try_move(&mut crates, command, index + 1, crane)?;
+        // clear the terminal and redraw from the top
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "move {} from {} to {}\n{}",
+            command.amount,
+            command.target,
+            command.destination,
+            crates.render(Some(command))
+        );
+    }
+    Ok(())
+}
+
+impl CrateTreeHelper for Stacks {
     fn aggregate_top_letters(&self) -> String {
-        self.par_iter()
-            .fold(
-                || String::new(),
-                |mut acc, (_, map)| {
-                    acc.push_str(&map.get_last());
-                    acc
-                },
-            )
-            .reduce(
-                || String::new(),
-                |mut acc, char| {
-                    acc.push_str(&char);
-                    acc
-                },
-            )
+        self.format_tops(TopFormat::Concatenated)
     }
 
-    fn get_safe(&self, index: u32) -> Vec<String> {
-        self.get(&index).map_or_else(
-            || {
-                tracing::error!("no create elem found at index {index}");
+    fn format_tops(&self, format: TopFormat) -> String {
+        let tops = self
+            .numbers
+            .iter()
+            .map(|number| (*number, self.top(*number)))
+            .collect::<BTreeMap<_, _>>();
+        match format {
+            TopFormat::Concatenated => tops.values().flatten().copied().collect(),
+            TopFormat::Comma => tops
+                .values()
+                .map(|top| top.unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(","),
+            TopFormat::Json => serde_json::to_string(&tops).unwrap_or_else(|err| {
+                tracing::error!("{err}");
                 std::process::exit(1)
-            },
-            std::clone::Clone::clone,
+            }),
+        }
+    }
+
+    // both stacks of a move, which must be different
+    fn stacks_mut(&mut self, target: u32, destination: u32) -> (&mut Vec<Crate>, &mut Vec<Crate>) {
+        let (from, to) = (self.index_safe(target), self.index_safe(destination));
+        if from == to {
+            tracing::error!("can't move from stack {target} to itself");
+            std::process::exit(1)
+        }
+        // split between the two so each half can be borrowed on its own
+        let (low, high) = self.stacks.split_at_mut(from.max(to));
+        if from < to {
+            (&mut low[from], &mut high[0])
+        } else {
+            (&mut high[0], &mut low[to])
+        }
+    }
+}
+
+/// how a crane carries `amount` crates from the top of one stack to another
+trait Crane {
+    fn lift(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, amount: usize);
+
+    // whether asking for more crates than a stack holds is an error, rather
+    // than the crane carrying what it can
+    fn strict(&self) -> bool {
+        true
+    }
+}
+
+/// one crate at a time, so a move reverses their order
+struct CrateMover9000;
+
+/// every crate in one go, keeping their order
+struct CrateMover9001;
+
+/// bulk moves split into lifts of at most `capacity` crates
+struct CapacityCrane {
+    capacity: usize,
+}
+
+/// bulk moves that can't reach the bottom `floor` crates of a stack, moves that
+/// would dig below it only carry what is in reach
+struct HeightLimitedCrane {
+    floor: usize,
+}
+
+// drains the top `amount` crates of `from`, bottom first
+fn take_top(from: &mut Vec<Crate>, amount: usize) -> std::vec::Drain<'_, Crate> {
+    if amount > from.len() {
+        tracing::error!("can't lift {amount} crates from a stack of {}", from.len());
+        std::process::exit(1)
+    }
+    from.drain(from.len() - amount..)
+}
+
+impl Crane for CrateMover9000 {
+    fn lift(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, amount: usize) {
+        to.extend(take_top(from, amount).rev());
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn lift(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, amount: usize) {
+        to.extend(take_top(from, amount));
+    }
+}
+
+impl Crane for CapacityCrane {
+    fn lift(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, amount: usize) {
+        let mut remaining = amount;
+        while remaining > 0 {
+            let lift = remaining.min(self.capacity);
+            to.extend(take_top(from, lift));
+            remaining -= lift;
+        }
+    }
+}
+
+impl Crane for HeightLimitedCrane {
+    fn lift(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, amount: usize) {
+        let reach = from.len().saturating_sub(self.floor);
+        if amount > reach {
+            tracing::warn!("only {reach} of {amount} crates are above the floor");
+        }
+        to.extend(take_top(from, amount.min(reach)));
+    }
+
+    fn strict(&self) -> bool {
+        false
+    }
+}
+
+// `--crane 9000`, `--crane 9001`, `--crane capacity:3` or `--crane height:2`
+fn crane_from_args() -> anyhow::Result<Option<Box<dyn Crane>>> {
+    let model = match flag_value("--crane") {
+        Some(model) => model,
+        None => return Ok(None),
+    };
+    let (kind, limit) = model.split_once(':').unwrap_or((&model, ""));
+    let limit = || match limit.parse::<usize>() {
+        Ok(limit) => Ok(limit),
+        Err(_) => anyhow::bail!("crane {kind} needs a number, eg {kind}:3"),
+    };
+    let crane: Box<dyn Crane> = match kind {
+        "9000" => Box::new(CrateMover9000),
+        "9001" => Box::new(CrateMover9001),
+        "capacity" => match limit()? {
+            0 => anyhow::bail!("a crane with no capacity can't move anything"),
+            capacity => Box::new(CapacityCrane { capacity }),
+        },
+        "height" => Box::new(HeightLimitedCrane { floor: limit()? }),
+        _ => anyhow::bail!("unknown crane {model}, expected 9000, 9001, capacity:N or height:N"),
+    };
+    Ok(Some(crane))
+}
+
+// checks each move against the stacks before running it, stopping at the first
+// one that can't be made
+fn try_execute(
+    crates: &mut Stacks,
+    instructions: &[Command],
+    crane: &dyn Crane,
+) -> Result<(), MoveError> {
+    for (index, command) in instructions.iter().enumerate() {
+        try_move(crates, command, index + 1, crane)?;
+    }
+    Ok(())
+}
+
+// a single move, `instruction` is its 1 based position in the program
+fn try_move(
+    crates: &mut Stacks,
+    command: &Command,
+    instruction: usize,
+    crane: &dyn Crane,
+) -> Result<(), MoveError> {
+    let Command {
+        amount,
+        target,
+        destination,
+        line,
+    } = *command;
+    for stack in [target, destination] {
+        if crates.index(stack).is_none() {
+            return Err(MoveError::UnknownStack {
+                line,
+                instruction,
+                stack,
+                stacks: BTreeMap::from(&*crates),
+            });
+        }
+    }
+    let held = crates.stacks[crates.index_safe(target)].len();
+    if amount as usize > held && crane.strict() {
+        return Err(MoveError::NotEnoughCrates {
+            line,
+            instruction,
+            stack: target,
+            amount,
+            held,
+            stacks: BTreeMap::from(&*crates),
+        });
+    }
+    if target != destination {
+        let (from, to) = crates.stacks_mut(target, destination);
+        crane.lift(from, to, amount as usize);
+    }
+    Ok(())
+}
+
+/// enough about one executed move to undo it, the crates it took from the
+/// source in their original order
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MoveRecord {
+    target: u32,
+    destination: u32,
+    taken: Vec<Crate>,
+}
+
+/// every move executed so far, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct MoveLog(Vec<MoveRecord>);
+
+impl MoveLog {
+    // undoes moves newest first until only `steps` remain applied
+    fn rewind_to(&mut self, crates: &mut Stacks, steps: usize) {
+        let keep = steps.min(self.0.len());
+        for record in self.0.drain(keep..).rev() {
+            if record.taken.is_empty() {
+                continue;
+            }
+            let (to, from) = crates.stacks_mut(record.destination, record.target);
+            to.truncate(to.len() - record.taken.len());
+            from.extend(record.taken);
+        }
+    }
+}
+
+// like `try_move`, also logging what the move took so it can be undone
+fn try_move_logged(
+    crates: &mut Stacks,
+    command: &Command,
+    instruction: usize,
+    crane: &dyn Crane,
+    log: &mut MoveLog,
+) -> Result<(), MoveError> {
+    // copy what the crane could take before it moves anything
+    let top = crates.stack(command.target).map_or(vec![], |from| {
+        from[from.len().saturating_sub(command.amount as usize)..].to_vec()
+    });
+    let before = crates.stack(command.destination).map_or(0, Vec::len);
+    try_move(crates, command, instruction, crane)?;
+    let landed = if command.target == command.destination {
+        0
+    } else {
+        crates.stack(command.destination).map_or(0, Vec::len) - before
+    };
+    log.0.push(MoveRecord {
+        target: command.target,
+        destination: command.destination,
+        taken: top[top.len() - landed..].to_vec(),
+    });
+    Ok(())
+}
+
+fn execute_logged(
+    crates: &mut Stacks,
+    instructions: &[Command],
+    crane: &dyn Crane,
+) -> Result<MoveLog, MoveError> {
+    let mut log = MoveLog::default();
+    for (index, command) in instructions.iter().enumerate() {
+        try_move_logged(crates, command, index + 1, crane, &mut log)?;
+    }
+    Ok(log)
+}
+
+/// where a crate started, counting depth down from the top of its stack, and
+/// every move that carried it as (instruction, from, to)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Provenance {
+    stack: u32,
+    depth: usize,
+    moves: Vec<(usize, u32, u32)>,
+}
+
+// the history of every crate, indexed by crate id
+fn provenance(initial: &Stacks, log: &MoveLog) -> Vec<Provenance> {
+    let mut crates = vec![None; initial.labels.len()];
+    for (number, stack) in initial.numbers.iter().zip(&initial.stacks) {
+        for (depth, id) in stack.iter().rev().enumerate() {
+            crates[*id as usize] = Some(Provenance {
+                stack: *number,
+                depth,
+                moves: vec![],
+            });
+        }
+    }
+    let mut crates = crates.into_iter().flatten().collect::<Vec<_>>();
+    for (index, record) in log.0.iter().enumerate() {
+        for id in &record.taken {
+            crates[*id as usize]
+                .moves
+                .push((index + 1, record.target, record.destination));
+        }
+    }
+    crates
+}
+
+fn print_provenance(initial: &Stacks, stacks: &Stacks, log: &MoveLog) {
+    let history = provenance(initial, log);
+    let origin = |id: Crate| {
+        let found = &history[id as usize];
+        format!(
+            "[{}] from stack {} depth {}",
+            stacks.labels[id as usize], found.stack, found.depth
         )
+    };
+    for (number, stack) in stacks.numbers.iter().zip(&stacks.stacks) {
+        let top = match stack.last() {
+            Some(top) => top,
+            None => {
+                println!("stack {number} is empty");
+                continue;
+            }
+        };
+        let moves = &history[*top as usize].moves;
+        println!(
+            "stack {number}: {}, moved {} times",
+            origin(*top),
+            moves.len()
+        );
+        for (instruction, from, to) in moves {
+            println!("  {instruction}: {from} -> {to}");
+        }
+    }
+    let unmoved = (0..history.len() as Crate)
+        .filter(|id| history[*id as usize].moves.is_empty())
+        .map(origin)
+        .collect::<Vec<_>>();
+    println!("never moved: {}", unmoved.len());
+    for crate_origin in unmoved {
+        println!("  {crate_origin}");
+    }
+    if let Some(busiest) =
+        (0..history.len() as Crate).max_by_key(|id| history[*id as usize].moves.len())
+    {
+        println!(
+            "most moved: {}, {} times",
+            origin(busiest),
+            history[busiest as usize].moves.len()
+        );
+    }
+}
+
+// a shorter program leaving every stack exactly as `instructions` does on a
+// CrateMover 9001, which keeps the order of the crates it carries so
+//
+// - `move n from A to B` then `move n from B to A` cancel out
+// - `move n from A to B` then `move n from B to C` is `move n from A to C`
+// - empty moves and moves onto the same stack do nothing
+//
+// moves between a pair can be stepped over as long as they touch none of its stacks
+fn optimize(instructions: &[Command]) -> Vec<Command> {
+    let mut program = instructions
+        .iter()
+        .filter(|command| command.amount > 0 && command.target != command.destination)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut index = 0;
+        while index < program.len() {
+            let first = program[index];
+            // stacks touched by the moves stepped over so far
+            let mut between = vec![];
+            for later in index + 1..program.len() {
+                let second = program[later];
+                let partner = second.target == first.destination && second.amount == first.amount;
+                if partner && !between.contains(&second.destination) {
+                    program.remove(later);
+                    if second.destination == first.target {
+                        program.remove(index);
+                    } else {
+                        program[index].destination = second.destination;
+                    }
+                    changed = true;
+                    break;
+                }
+                if [first.target, first.destination]
+                    .iter()
+                    .any(|stack| *stack == second.target || *stack == second.destination)
+                {
+                    break;
+                }
+                between.extend([second.target, second.destination]);
+            }
+            index += 1;
+        }
     }
+    program
+}
+
+// runs both programs on a CrateMover 9001, true when every stack ends the same
+fn same_result(
+    crates: &Stacks,
+    instructions: &[Command],
+    optimized: &[Command],
+) -> Result<bool, MoveError> {
+    let (mut expected, mut found) = (crates.clone(), crates.clone());
+    try_execute(&mut expected, instructions, &CrateMover9001)?;
+    try_execute(&mut found, optimized, &CrateMover9001)?;
+    Ok(expected == found)
+}
+
+/// the stacks after the first `instruction` moves, enough to pick a run back up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Snapshot {
+    instruction: usize,
+    stacks: BTreeMap<u32, Vec<String>>,
+}
+
+fn snapshot_at(
+    crates: &Stacks,
+    instructions: &[Command],
+    crane: &dyn Crane,
+    at: usize,
+) -> Result<Snapshot, MoveError> {
+    let instruction = at.min(instructions.len());
+    let mut crates = crates.clone();
+    try_execute(&mut crates, &instructions[..instruction], crane)?;
+    Ok(Snapshot {
+        instruction,
+        stacks: BTreeMap::from(&crates),
+    })
 }
 
-fn part1(lines: &[&str]) -> String {
+// runs `instructions` on top of a snapshot, errors number them from where it left off
+fn resume(
+    snapshot: &Snapshot,
+    instructions: &[Command],
+    crane: &dyn Crane,
+) -> Result<Stacks, MoveError> {
+    let mut crates = Stacks::from(&snapshot.stacks);
+    for (index, command) in instructions.iter().enumerate() {
+        try_move(
+            &mut crates,
+            command,
+            snapshot.instruction + index + 1,
+            crane,
+        )?;
+    }
+    Ok(crates)
+}
+
+// parses and runs the whole input, reporting the first bad move
+fn validate(lines: &[&str], crane: &dyn Crane) -> anyhow::Result<usize> {
+    let (crates, skip) = lines.generate_tree()?;
+    let instructions = lines.run_instructions(skip, &mut Stacks::from(&crates), crane, 0)?;
+    Ok(instructions.len())
+}
+
+// the stacks once every instruction has been run by `crane`
+fn run_stacks(lines: &[&str], crane: &dyn Crane) -> anyhow::Result<Stacks> {
+    let (crates, skip) = lines.generate_tree()?;
+    let mut crates = Stacks::from(&crates);
+    lines.run_instructions(skip, &mut crates, crane, 0)?;
+    Ok(crates)
+}
+
+// top letters once every instruction has been run by `crane`
+fn run(lines: &[&str], crane: &dyn Crane) -> anyhow::Result<String> {
+    Ok(run_stacks(lines, crane)?.aggregate_top_letters())
+}
+
+fn part1(lines: &[&str]) -> anyhow::Result<String> {
     let start = std::time::Instant::now();
-    let (mut crates, skip) = lines.generate_tree();
-    // // parse instructions
-    let instructions = lines.generate_instructions(skip);
-    // execute instructions - afaik must be executed sequentially
-    for command in instructions {
-        let Command {
-            amount,
-            target,
-            destination,
-        } = command;
-        let mut target_crates = crates.get_safe(target);
-        let mut destination_crates = crates.get_safe(destination);
-        for _ in 0..amount {
-            destination_crates.push(target_crates.pop_last());
-        }
-        crates.insert(target, target_crates.clone());
-        crates.insert(destination, destination_crates.clone());
-    }
-    // get top letters of each stack
-    let answer = crates.aggregate_top_letters();
+    let answer = run(lines, &CrateMover9000)?;
     tracing::info!("operation complete in: {:#?}", start.elapsed());
-    answer
+    Ok(answer)
 }
 
-fn part2(lines: &[&str]) -> String {
+fn part2(lines: &[&str]) -> anyhow::Result<String> {
     let start = std::time::Instant::now();
-    let (mut crates, skip) = lines.generate_tree();
-    // // parse instructions
-    let instructions = lines.generate_instructions(skip);
-    // execute instructions - afaik must be executed sequentially
-    for command in instructions {
-        let Command {
-            amount,
-            target,
-            destination,
-        } = command;
-        let mut target_crates = crates.get_safe(target);
-        let mut destination_crates = crates.get_safe(destination);
-        let mut temp = vec![];
-        for _ in 0..amount {
-            temp.push(target_crates.pop_last());
-        }
-        temp.reverse();
-        destination_crates.append(&mut temp);
-        crates.insert(target, target_crates.clone());
-        crates.insert(destination, destination_crates.clone());
-    }
-    // get top letters of each stack
-    let answer = crates.aggregate_top_letters();
+    let answer = run(lines, &CrateMover9001)?;
     tracing::info!("operation complete in: {:#?}", start.elapsed());
-    answer
+    Ok(answer)
 }
 
-pub fn main() {
+pub fn main() -> anyhow::Result<()> {
     init_logger();
-    let lines = INPUT.par_lines().collect::<Vec<_>>();
-    let answer = part1(&lines);
+    // `--input <path>` for a raw puzzle input, or a broken one to validate
+    let input = match flag_value("--input") {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => INPUT.to_string(),
+    };
+    let lines = input.par_lines().collect::<Vec<_>>();
+    let crane = crane_from_args()?;
+    // `--top-format concat|comma|json`
+    let format = flag_value("--top-format")
+        .map_or(Ok(TopFormat::Concatenated), |format| format.parse())
+        .map_err(anyhow::Error::msg)?;
+    if has_flag("--validate") {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let instructions = validate(&lines, crane.as_ref())?;
+        println!("all {instructions} instructions are valid");
+        return Ok(());
+    }
+    // `--rewind-to N [--stack S]` runs every move then rewinds to step N
+    if let Some(steps) = flag_value("--rewind-to") {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let (crates, skip) = lines.generate_tree()?;
+        let mut crates = Stacks::from(&crates);
+        let instructions = lines.run_instructions(skip, &mut crates.clone(), crane.as_ref(), 0)?;
+        let mut log = execute_logged(&mut crates, &instructions, crane.as_ref())?;
+        log.rewind_to(&mut crates, steps.parse()?);
+        match flag_value("--stack") {
+            Some(stack) => println!("{}", crates.top(stack.parse()?).unwrap_or("(empty)")),
+            None => println!("{}", crates.format_tops(format)),
+        }
+        return Ok(());
+    }
+    // `--snapshot-at N` prints the stacks after N moves as json
+    if let Some(at) = flag_value("--snapshot-at") {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let (crates, skip) = lines.generate_tree()?;
+        let crates = Stacks::from(&crates);
+        let instructions = lines.run_instructions(skip, &mut crates.clone(), crane.as_ref(), 0)?;
+        let snapshot = snapshot_at(&crates, &instructions, crane.as_ref(), at.parse()?)?;
+        println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        return Ok(());
+    }
+    // `--from-snapshot path` carries on with the moves in `--moves path`, or
+    // the input's moves after the ones the snapshot has already made
+    if let Some(path) = flag_value("--from-snapshot") {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let snapshot = serde_json::from_str::<Snapshot>(&std::fs::read_to_string(path)?)?;
+        let instructions = match flag_value("--moves") {
+            Some(moves) => {
+                let moves = std::fs::read_to_string(moves)?;
+                moves.lines().collect::<Vec<_>>().run_instructions(
+                    0,
+                    &mut Stacks::from(&snapshot.stacks),
+                    crane.as_ref(),
+                    snapshot.instruction,
+                )?
+            }
+            None => {
+                let (crates, skip) = lines.generate_tree()?;
+                let instructions =
+                    lines.run_instructions(skip, &mut Stacks::from(&crates), crane.as_ref(), 0)?;
+                instructions[snapshot.instruction.min(instructions.len())..].to_vec()
+            }
+        };
+        let crates = resume(&snapshot, &instructions, crane.as_ref())?;
+        println!("{}", crates.format_tops(format));
+        return Ok(());
+    }
+    // `--optimize` prints a shorter program for the CrateMover 9001
+    if has_flag("--optimize") {
+        let (crates, skip) = lines.generate_tree()?;
+        let crates = Stacks::from(&crates);
+        let instructions = lines.run_instructions(skip, &mut crates.clone(), &CrateMover9001, 0)?;
+        let optimized = optimize(&instructions);
+        if !same_result(&crates, &instructions, &optimized)? {
+            anyhow::bail!("optimized program ends in a different state");
+        }
+        tracing::info!("{} moves down to {}", instructions.len(), optimized.len());
+        for command in optimized {
+            println!("{command}");
+        }
+        return Ok(());
+    }
+    if has_flag("--provenance") {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let (crates, skip) = lines.generate_tree()?;
+        let initial = Stacks::from(&crates);
+        let instructions = lines.run_instructions(skip, &mut initial.clone(), crane.as_ref(), 0)?;
+        let mut stacks = initial.clone();
+        let log = execute_logged(&mut stacks, &instructions, crane.as_ref())?;
+        print_provenance(&initial, &stacks, &log);
+        return Ok(());
+    }
+    if has_flag("--animate") || has_flag("--step") || flag_value("--at").is_some() {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        return show(&lines, crane.as_ref());
+    }
+    if let Some(crane) = crane {
+        println!(
+            "{}",
+            run_stacks(&lines, crane.as_ref())?.format_tops(format)
+        );
+        return Ok(());
+    }
+    if format != TopFormat::Concatenated {
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            println!("{}", run_stacks(&lines, crane)?.format_tops(format));
+        }
+        return Ok(());
+    }
+    let answer = part1(&lines)?;
     tracing::info!("{answer}");
-    let answer = part2(&lines);
+    let answer = part2(&lines)?;
     tracing::info!("{answer}");
+    Ok(())
 }
 
 #[allow(unused_imports)]
@@ -171,14 +1120,642 @@ pub fn main() {
 pub mod tests {
 
     use super::*;
+    use advent_of_code_2022::{types::VecHelpers, utils::Xorshift};
+
+    trait InstructionHelper {
+        fn generate_instructions(&self, skip: usize) -> Vec<Command>;
+    }
+
+    impl InstructionHelper for [&str] {
+        fn generate_instructions(&self, skip: usize) -> Vec<Command> {
+            self.iter()
+                .enumerate()
+                .skip(skip)
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(index, line)| {
+                    parse_command(line, index + 1)
+                        .unwrap_or_else(|| panic!("line {}: malformed {line}", index + 1))
+                })
+                .collect()
+        }
+    }
+
+    // executes every instruction sequentially with the given crane
+    fn execute(crates: &mut Stacks, instructions: &[Command], crane: &dyn Crane) {
+        try_execute(crates, instructions, crane).unwrap_or_else(|err| panic!("{err}"));
+    }
 
     #[test]
-    fn day5_tests() {
+    fn day5_tests() -> anyhow::Result<()> {
         init_logger();
         let lines = INPUT.par_lines().collect::<Vec<_>>();
-        let answer = part1(&lines);
+        let answer = part1(&lines)?;
         assert_eq!(answer, "VPCDMSLWJ");
-        let answer = part2(&lines);
+        let answer = part2(&lines)?;
         assert_eq!(answer, "TPWCGNCCG");
+        Ok(())
+    }
+
+    #[test]
+    fn day5_drawing_tests() -> anyhow::Result<()> {
+        init_logger();
+        let example = [
+            "    [D]    ",
+            "[N] [C]",
+            "[Z] [M] [P]",
+            " 1   2   3 ",
+            "",
+            "move 1 from 2 to 1",
+            "move 3 from 1 to 3",
+            "move 2 from 2 to 1",
+            "move 1 from 1 to 2",
+        ];
+        let (crates, skip) = example.generate_tree()?;
+        assert_eq!(skip, 5);
+        assert_eq!(
+            crates.get(&1),
+            Some(&vec!["Z".to_string(), "N".to_string()])
+        );
+        assert_eq!(crates.get(&3), Some(&vec!["P".to_string()]));
+        assert_eq!(part1(&example)?, "CMZ");
+        assert_eq!(part2(&example)?, "MCD");
+        // eleven stacks, the last two numbered with two digits
+        let wide = [
+            "[A]                                     [K]",
+            "[B] [C]     [D] [E] [F] [G] [H] [I] [J] [M]",
+            " 1   2   3   4   5   6   7   8   9  10  11",
+        ];
+        let (crates, _) = wide.generate_tree()?;
+        assert_eq!(crates.len(), 11);
+        assert_eq!(crates.get(&3), Some(&vec![]));
+        assert_eq!(crates.get(&10), Some(&vec!["J".to_string()]));
+        assert_eq!(
+            crates.get(&11),
+            Some(&vec!["M".to_string(), "K".to_string()])
+        );
+        assert_eq!(
+            wide.generate_tree()?.0.get(&1),
+            Some(&vec!["B".to_string(), "A".to_string()])
+        );
+        // a listing whose last stack is empty isn't mistaken for a footer
+        let listing = ["1 [A] [B]", "2", "", "move 1 from 1 to 2"];
+        assert_eq!(
+            listing.generate_tree()?,
+            (
+                BTreeMap::from([(1, vec!["A".to_string(), "B".to_string()]), (2, vec![])]),
+                3
+            )
+        );
+        assert_eq!(part1(&listing)?, "AB");
+        assert_eq!(["1", "2"].generate_tree()?.0.len(), 2);
+        // the real input drawn out again parses to the same stacks
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (listed, skip) = lines.generate_tree()?;
+        let height = listed.values().map(Vec::len).max().unwrap_or_default();
+        let mut drawn = (0..height)
+            .rev()
+            .map(|level| {
+                let row = listed
+                    .values()
+                    .map(|stack| {
+                        stack
+                            .get(level)
+                            .map_or("   ".to_string(), |c| format!("[{c}]"))
+                    })
+                    .collect::<Vec<_>>();
+                row.join(" ").trim_end().to_string()
+            })
+            .collect::<Vec<_>>();
+        drawn.push(
+            listed
+                .keys()
+                .map(|num| format!(" {num} "))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        drawn.extend(lines[skip - 1..].iter().map(|line| (*line).to_string()));
+        let drawn = drawn.iter().map(String::as_str).collect::<Vec<_>>();
+        // the drawn header is every row plus its footer, then the blank line
+        assert_eq!(drawn.generate_tree()?, (listed, height + 2));
+        assert_eq!(part1(&drawn)?, "VPCDMSLWJ");
+        assert_eq!(part2(&drawn)?, "TPWCGNCCG");
+        // a bad stack number is reported with its line rather than exiting
+        let bad = ["1 [A]", "two [B]", "", "move 1 from 1 to 2"];
+        assert_eq!(
+            bad.generate_tree().map_err(|err| err.to_string()).err(),
+            Some("line 2: error parsing stack number two: invalid digit found in string".into())
+        );
+        assert!(part1(&bad).is_err());
+        // so is a crate label that never closes, in either layout
+        let unclosed = ["    [D]", "[N] [C", " 1   2", "", "move 1 from 2 to 1"];
+        assert_eq!(
+            unclosed
+                .generate_tree()
+                .map_err(|err| err.to_string())
+                .err(),
+            Some("line 2: crate opened at column 4 is never closed in [N] [C".into())
+        );
+        assert!(["1 [A] [B", "", "move 1 from 1 to 1"]
+            .generate_tree()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day5_crane_tests() -> anyhow::Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        // the limited cranes fall back to the two puzzle models at their extremes
+        assert_eq!(run(&lines, &CapacityCrane { capacity: 1 })?, "VPCDMSLWJ");
+        assert_eq!(run(&lines, &CapacityCrane { capacity: 100 })?, "TPWCGNCCG");
+        assert_eq!(run(&lines, &HeightLimitedCrane { floor: 0 })?, "TPWCGNCCG");
+        let stack = |crates: &str| crates.chars().map(u32::from).collect::<Vec<_>>();
+        let text = |stack: Vec<Crate>| {
+            stack
+                .into_iter()
+                .filter_map(char::from_u32)
+                .collect::<String>()
+        };
+        let lift = |crane: &dyn Crane, amount: usize| {
+            let (mut from, mut to) = (stack("ABCDE"), stack("Z"));
+            crane.lift(&mut from, &mut to, amount);
+            (text(from), text(to))
+        };
+        assert_eq!(lift(&CrateMover9000, 3), ("AB".into(), "ZEDC".into()));
+        assert_eq!(lift(&CrateMover9001, 3), ("AB".into(), "ZCDE".into()));
+        assert_eq!(
+            lift(&CapacityCrane { capacity: 2 }, 5),
+            ("".into(), "ZDEBCA".into())
+        );
+        assert_eq!(
+            lift(&HeightLimitedCrane { floor: 3 }, 4),
+            ("ABC".into(), "ZDE".into())
+        );
+        Ok(())
+    }
+
+    // the old executor, cloning both stacks out of the map on every move
+    fn legacy_execute(crates: &mut BTreeMap<u32, Vec<String>>, instructions: &[Command]) {
+        for command in instructions {
+            let mut target_crates = crates.get(&command.target).cloned().unwrap_or_default();
+            let mut destination_crates = crates
+                .get(&command.destination)
+                .cloned()
+                .unwrap_or_default();
+            for _ in 0..command.amount {
+                destination_crates.push(target_crates.pop_last());
+            }
+            crates.insert(command.target, target_crates);
+            crates.insert(command.destination, destination_crates);
+        }
+    }
+
+    // `height` crates on each of `count` stacks and `moves` random valid moves
+    fn stress(
+        count: u32,
+        height: usize,
+        moves: usize,
+    ) -> (BTreeMap<u32, Vec<String>>, Vec<Command>) {
+        let crates = (1..=count)
+            .map(|number| {
+                (
+                    number,
+                    (0..height).map(|n| format!("{number}.{n}")).collect(),
+                )
+            })
+            .collect::<BTreeMap<_, Vec<String>>>();
+        let mut sizes = vec![height; count as usize];
+        let mut rng = Xorshift::new(0x9e37_79b9_7f4a_7c15);
+        let mut next = |bound: usize| (rng.next_u64() % bound as u64) as usize;
+        let mut instructions = Vec::with_capacity(moves);
+        while instructions.len() < moves {
+            let (target, destination) = (next(count as usize), next(count as usize));
+            if target == destination || sizes[target] == 0 {
+                continue;
+            }
+            let amount = 1 + next(sizes[target].min(50));
+            sizes[target] -= amount;
+            sizes[destination] += amount;
+            instructions.push(Command {
+                amount: amount as u32,
+                target: target as u32 + 1,
+                destination: destination as u32 + 1,
+                line: instructions.len() + 1,
+            });
+        }
+        (crates, instructions)
+    }
+
+    #[test]
+    fn day5_stacks_tests() {
+        init_logger();
+        let (crates, instructions) = stress(9, 200, 5_000);
+        let mut stacks = Stacks::from(&crates);
+        assert_eq!(BTreeMap::from(&stacks), crates);
+        execute(&mut stacks, &instructions, &CrateMover9000);
+        let mut expected = crates;
+        legacy_execute(&mut expected, &instructions);
+        assert_eq!(BTreeMap::from(&stacks), expected);
+        // stack numbers that don't start at 1 or skip some
+        let sparse = [(2, vec!["A".to_string()]), (5, vec![]), (9, vec![])];
+        let stacks = Stacks::from(&sparse.into_iter().collect::<BTreeMap<_, _>>());
+        assert_eq!(stacks.index(2), Some(0));
+        assert_eq!(stacks.index(5), Some(1));
+        assert_eq!(stacks.index(9), Some(2));
+        assert_eq!(stacks.index(3), None);
+        assert_eq!(stacks.index(1), None);
+    }
+
+    // LOG_LEVEL=info cargo test --bin day_5 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn day5_benchmark() {
+        init_logger();
+        let (crates, instructions) = stress(20, 50_000, 2_000_000);
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut stacks = Stacks::from(&crates);
+            let start = std::time::Instant::now();
+            execute(&mut stacks, &instructions, crane);
+            tracing::info!(
+                "2,000,000 moves over 1,000,000 crates: {:#?}",
+                start.elapsed()
+            );
+            assert_eq!(stacks.stacks.iter().map(Vec::len).sum::<usize>(), 1_000_000);
+        }
+        // the cloning executor only gets a slice of the same program
+        let mut expected = crates;
+        let start = std::time::Instant::now();
+        legacy_execute(&mut expected, &instructions[..2_000]);
+        tracing::info!("2,000 moves with the old executor: {:#?}", start.elapsed());
+    }
+
+    #[test]
+    fn day5_validation_tests() {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        assert_eq!(validate(&lines, &CrateMover9001).ok(), Some(502));
+        let with = |bad: &str| {
+            let mut lines = ["1 [A] [B]", "2 [C]", "", "move 1 from 1 to 2"].to_vec();
+            lines.push(bad);
+            validate(&lines, &CrateMover9000).map_err(|err| err.downcast::<MoveError>().ok())
+        };
+        // a malformed line is only reached once the moves before it have run
+        let stacks = [
+            (1, vec!["A".to_string()]),
+            (2, vec!["C".into(), "B".into()]),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+        let err = with("move 1 from");
+        assert_eq!(
+            err,
+            Err(Some(MoveError::Malformed {
+                line: 5,
+                instruction: 2,
+                text: "move 1 from".into(),
+                stacks: stacks.clone()
+            }))
+        );
+        assert_eq!(
+            err.map_err(|err| err.map(|err| err.to_string())),
+            Err(Some(
+                "line 5: instruction 2 expected `move N from A to B`, found `move 1 from`\n1: A\n2: C B"
+                    .into()
+            ))
+        );
+        assert!(with("move one from 1 to 2").is_err());
+        assert_eq!(
+            with("move 1 from 2 to 12"),
+            Err(Some(MoveError::UnknownStack {
+                line: 5,
+                instruction: 2,
+                stack: 12,
+                stacks: stacks.clone()
+            }))
+        );
+        let err = with("move 5 from 1 to 2");
+        assert_eq!(
+            err,
+            Err(Some(MoveError::NotEnoughCrates {
+                line: 5,
+                instruction: 2,
+                stack: 1,
+                amount: 5,
+                held: 1,
+                stacks
+            }))
+        );
+        assert_eq!(
+            err.map_err(|err| err.map(|err| err.to_string())),
+            Err(Some(
+                "line 5: instruction 2 moves 5 crates but stack 1 holds only 1\n1: A\n2: C B"
+                    .into()
+            ))
+        );
+        assert_eq!(with("move 2 from 2 to 1"), Ok(2));
+    }
+
+    #[test]
+    fn day5_render_tests() -> anyhow::Result<()> {
+        init_logger();
+        let example = [
+            "    [D]",
+            "[N] [C]",
+            "[Z] [M] [P]",
+            " 1   2   3 ",
+            "",
+            "move 1 from 2 to 1",
+        ];
+        let (crates, skip) = example.generate_tree()?;
+        let mut stacks = Stacks::from(&crates);
+        assert_eq!(stacks.render(None), example[..4].join("\n"));
+        let instructions = example.generate_instructions(skip);
+        execute(&mut stacks, &instructions, &CrateMover9000);
+        let moved = stacks.render(Some(&instructions[0]));
+        assert!(moved.starts_with("\x1b[32m[D]\x1b[0m\n[N] \x1b[33m[C]\x1b[0m\n"));
+        let plain = moved
+            .replace(SOURCE_COLOUR, "")
+            .replace(DESTINATION_COLOUR, "")
+            .replace(RESET_COLOUR, "");
+        assert_eq!(plain, stacks.render(None));
+        // the real input drawn and parsed again
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (crates, _) = lines.generate_tree()?;
+        let drawn = Stacks::from(&crates).render(None);
+        let drawn = drawn.lines().collect::<Vec<_>>();
+        assert_eq!(drawn.generate_tree()?.0, crates);
+        assert_eq!(parse_delay("50ms"), Ok(Duration::from_millis(50)));
+        assert_eq!(parse_delay("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_delay("15"), Ok(Duration::from_millis(15)));
+        assert!(parse_delay("fast").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day5_rewind_tests() -> anyhow::Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (crates, skip) = lines.generate_tree()?;
+        let initial = Stacks::from(&crates);
+        let instructions = lines.generate_instructions(skip);
+        let cranes: [(&dyn Crane, &str); 4] = [
+            (&CrateMover9000, "VPCDMSLWJ"),
+            (&CrateMover9001, "TPWCGNCCG"),
+            (&CapacityCrane { capacity: 3 }, "SZWVLCBTD"),
+            (&HeightLimitedCrane { floor: 1 }, "DZLBSPGPM"),
+        ];
+        for (crane, answer) in cranes {
+            let mut stacks = initial.clone();
+            let mut log = execute_logged(&mut stacks, &instructions, crane).unwrap_or_default();
+            assert_eq!(stacks.aggregate_top_letters(), answer);
+            // part way back matches running just that many moves forward
+            log.rewind_to(&mut stacks, 137);
+            let mut expected = initial.clone();
+            execute(&mut expected, &instructions[..137], crane);
+            assert_eq!(stacks, expected);
+            assert_eq!(stacks.top(4), expected.top(4));
+            log.rewind_to(&mut stacks, 0);
+            assert_eq!(stacks, initial);
+            assert!(log.0.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn day5_label_tests() -> anyhow::Result<()> {
+        init_logger();
+        let listing = [
+            "1 [AB12] [x]",
+            "2",
+            "3 [long label] [C]",
+            "",
+            "move 1 from 1 to 2",
+        ];
+        let (crates, skip) = listing.generate_tree()?;
+        assert_eq!(
+            crates.get(&1),
+            Some(&vec!["AB12".to_string(), "x".to_string()])
+        );
+        assert_eq!(crates.get(&2), Some(&vec![]));
+        assert_eq!(
+            crates.get(&3),
+            Some(&vec!["long label".to_string(), "C".to_string()])
+        );
+        let mut stacks = Stacks::from(&crates);
+        assert_eq!(stacks.format_tops(TopFormat::Comma), "x,,C");
+        assert_eq!(stacks.format_tops(TopFormat::Concatenated), "xC");
+        assert_eq!(
+            stacks.format_tops(TopFormat::Json),
+            r#"{"1":"x","2":null,"3":"C"}"#
+        );
+        execute(
+            &mut stacks,
+            &listing.generate_instructions(skip),
+            &CrateMover9000,
+        );
+        assert_eq!(stacks.format_tops(TopFormat::Comma), "AB12,x,C");
+        // wide labels drawn and parsed again
+        let drawn = stacks.render(None);
+        assert!(drawn.starts_with(&format!("{}[C]\n", " ".repeat(30))));
+        let drawn = drawn.lines().collect::<Vec<_>>();
+        assert_eq!(drawn.generate_tree()?.0, BTreeMap::from(&stacks));
+        assert_eq!("json".parse(), Ok(TopFormat::Json));
+        assert!("yaml".parse::<TopFormat>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day5_provenance_tests() -> anyhow::Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (crates, skip) = lines.generate_tree()?;
+        let initial = Stacks::from(&crates);
+        let instructions = lines.generate_instructions(skip);
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut stacks = initial.clone();
+            let log = execute_logged(&mut stacks, &instructions, crane).unwrap_or_default();
+            let history = provenance(&initial, &log);
+            assert_eq!(history.len(), initial.labels.len());
+            // every crate carried by every move is accounted for once
+            let carried = history.iter().map(|found| found.moves.len()).sum::<usize>();
+            let moved = instructions
+                .iter()
+                .map(|command| command.amount as usize)
+                .sum::<usize>();
+            assert_eq!(carried, moved);
+            for (number, stack) in stacks.numbers.iter().zip(&stacks.stacks) {
+                for id in stack {
+                    let found = &history[*id as usize];
+                    // each crate ends where its last move put it
+                    match found.moves.last() {
+                        Some((_, _, to)) => assert_eq!(to, number),
+                        None => assert_eq!(found.stack, *number),
+                    }
+                    // and each move picks it up where the one before left it
+                    let mut at = found.stack;
+                    for (_, from, to) in &found.moves {
+                        assert_eq!(*from, at);
+                        at = *to;
+                    }
+                }
+            }
+        }
+        let example = [
+            "1 [A] [B]",
+            "2 [C]",
+            "",
+            "move 1 from 1 to 2",
+            "move 2 from 2 to 1",
+        ];
+        let (crates, skip) = example.generate_tree()?;
+        let initial = Stacks::from(&crates);
+        let mut stacks = initial.clone();
+        let log = execute_logged(
+            &mut stacks,
+            &example.generate_instructions(skip),
+            &CrateMover9001,
+        )
+        .unwrap_or_default();
+        let history = provenance(&initial, &log);
+        assert_eq!(
+            history,
+            vec![
+                Provenance {
+                    stack: 1,
+                    depth: 1,
+                    moves: vec![]
+                },
+                Provenance {
+                    stack: 1,
+                    depth: 0,
+                    moves: vec![(1, 1, 2), (2, 2, 1)]
+                },
+                Provenance {
+                    stack: 2,
+                    depth: 0,
+                    moves: vec![(2, 2, 1)]
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day5_optimize_tests() -> anyhow::Result<()> {
+        init_logger();
+        let program = |moves: &[(u32, u32, u32)]| {
+            moves
+                .iter()
+                .enumerate()
+                .map(|(index, (amount, target, destination))| Command {
+                    amount: *amount,
+                    target: *target,
+                    destination: *destination,
+                    line: index + 1,
+                })
+                .collect::<Vec<_>>()
+        };
+        let moves = |program: Vec<Command>| {
+            program
+                .iter()
+                .map(|command| (command.amount, command.target, command.destination))
+                .collect::<Vec<_>>()
+        };
+        // back and forth, chained and no-op moves
+        assert_eq!(moves(optimize(&program(&[(2, 1, 2), (2, 2, 1)]))), vec![]);
+        assert_eq!(
+            moves(optimize(&program(&[
+                (2, 1, 2),
+                (2, 2, 3),
+                (0, 3, 1),
+                (1, 4, 4)
+            ]))),
+            vec![(2, 1, 3)]
+        );
+        // stepping over a move on other stacks, but not one onto the final stack
+        assert_eq!(
+            moves(optimize(&program(&[(2, 1, 2), (1, 4, 5), (2, 2, 3)]))),
+            vec![(2, 1, 3), (1, 4, 5)]
+        );
+        assert_eq!(
+            moves(optimize(&program(&[(2, 1, 2), (1, 4, 3), (2, 2, 3)]))),
+            vec![(2, 1, 2), (1, 4, 3), (2, 2, 3)]
+        );
+        // a different amount doesn't undo the move
+        assert_eq!(
+            moves(optimize(&program(&[(2, 1, 2), (1, 2, 1)]))),
+            vec![(2, 1, 2), (1, 2, 1)]
+        );
+        // shorter on the real input and on random programs, and still the same result
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (crates, skip) = lines.generate_tree()?;
+        let instructions = lines.generate_instructions(skip);
+        let optimized = optimize(&instructions);
+        assert!(optimized.len() < instructions.len());
+        assert_eq!(
+            same_result(&Stacks::from(&crates), &instructions, &optimized),
+            Ok(true)
+        );
+        let (crates, instructions) = stress(4, 6, 2_000);
+        let optimized = optimize(&instructions);
+        assert!(optimized.len() < instructions.len());
+        assert_eq!(
+            same_result(&Stacks::from(&crates), &instructions, &optimized),
+            Ok(true)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day5_snapshot_tests() -> anyhow::Result<()> {
+        init_logger();
+        let lines = INPUT.par_lines().collect::<Vec<_>>();
+        let (crates, skip) = lines.generate_tree()?;
+        let crates = Stacks::from(&crates);
+        let instructions = lines.generate_instructions(skip);
+        let cranes: [(&dyn Crane, &str); 2] = [
+            (&CrateMover9000, "VPCDMSLWJ"),
+            (&CrateMover9001, "TPWCGNCCG"),
+        ];
+        for (crane, answer) in cranes {
+            let mut expected = crates.clone();
+            execute(&mut expected, &instructions, crane);
+            assert_eq!(expected.aggregate_top_letters(), answer);
+            for at in [0, 1, 137, 250, 501, 502] {
+                let snapshot = snapshot_at(&crates, &instructions, crane, at)
+                    .unwrap_or_else(|err| panic!("{err}"));
+                assert_eq!(snapshot.instruction, at);
+                let json = serde_json::to_string(&snapshot).unwrap_or_default();
+                assert_eq!(
+                    serde_json::from_str::<Snapshot>(&json).ok(),
+                    Some(snapshot.clone())
+                );
+                let resumed = resume(&snapshot, &instructions[at..], crane);
+                let resumed = resumed.map(|crates| BTreeMap::from(&crates));
+                assert_eq!(resumed, Ok(BTreeMap::from(&expected)));
+            }
+        }
+        // errors while resuming count from the snapshot's instruction
+        let snapshot = Snapshot {
+            instruction: 10,
+            stacks: [(1, vec!["A".to_string()])].into_iter().collect(),
+        };
+        let bad = ["move 1 from 1 to 2"].generate_instructions(0);
+        assert!(matches!(
+            resume(&snapshot, &bad, &CrateMover9001),
+            Err(MoveError::UnknownStack {
+                instruction: 11,
+                stack: 2,
+                ..
+            })
+        ));
+        let json = r#"{"instruction":3,"stacks":{"1":["A","B"],"2":[]}}"#;
+        assert_eq!(
+            serde_json::from_str::<Snapshot>(json)
+                .map(|snapshot| snapshot.stacks.len())
+                .ok(),
+            Some(2)
+        );
+        Ok(())
     }
 }
@@ -29,11 +29,7 @@ impl CharHelpers for Vec<String> {
 
 fn part1() -> usize {
     let start = std::time::Instant::now();
-    let chars = INPUT
-        .chars()
-        .into_iter()
-        .map(|a| a.to_string())
-        .collect::<Vec<_>>();
+    let chars = INPUT.chars().map(|a| a.to_string()).collect::<Vec<_>>();
     let index = chars.find_unique_marker(4);
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     index
@@ -41,11 +37,7 @@ fn part1() -> usize {
 
 fn part2() -> usize {
     let start = std::time::Instant::now();
-    let chars = INPUT
-        .chars()
-        .into_iter()
-        .map(|a| a.to_string())
-        .collect::<Vec<_>>();
+    let chars = INPUT.chars().map(|a| a.to_string()).collect::<Vec<_>>();
     let index = chars.find_unique_marker(14);
     tracing::info!("operation complete in: {:#?}", start.elapsed());
     index
@@ -1,7 +1,15 @@
 pub mod utils {
+    use std::sync::Once;
     use tracing_subscriber::FmtSubscriber;
 
+    static LOGGER: Once = Once::new();
+
+    // safe to call more than once, eg from every test in a binary
     pub fn init_logger() {
+        LOGGER.call_once(set_logger);
+    }
+
+    fn set_logger() {
         if cfg!(debug_assertions) {
             use dotenv::dotenv;
             dotenv().ok();
@@ -25,6 +33,45 @@ pub mod utils {
             }
         }
     }
+
+    /// true when `flag` was passed on the command line, eg `--explain`
+    pub fn has_flag(flag: &str) -> bool {
+        std::env::args().any(|arg| arg == flag)
+    }
+
+    /// value passed after `flag`, either as `--flag value` or `--flag=value`
+    pub fn flag_value(flag: &str) -> Option<String> {
+        let mut args = std::env::args();
+        while let Some(arg) = args.next() {
+            if arg == flag {
+                return args.next();
+            }
+            if let Some(value) = arg
+                .strip_prefix(flag)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                return Some(value.to_string());
+            }
+        }
+        None
+    }
+
+    /// seeded xorshift generator, the same seed always gives the same numbers
+    pub struct Xorshift(u64);
+
+    impl Xorshift {
+        pub fn new(seed: u64) -> Self {
+            // xorshift gets stuck on zero
+            Self(seed.max(1))
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
 }
 
 pub mod types {
@@ -62,25 +109,19 @@ pub mod types {
         }
 
         fn pop_last(&mut self) -> String {
-            self.pop().map_or_else(
-                || {
-                    tracing::error!("no elem found for pop");
-                    std::process::exit(1)
-                },
-                |str| str,
-            )
+            self.pop().unwrap_or_else(|| {
+                tracing::error!("no elem found for pop");
+                std::process::exit(1)
+            })
         }
     }
 
     impl StringHelpers for String {
         fn parse_safe<T: FromStr>(&self) -> T {
-            self.parse::<T>().map_or_else(
-                |_| {
-                    tracing::error!("error parsing integer");
-                    std::process::exit(1)
-                },
-                |int| int,
-            )
+            self.parse::<T>().unwrap_or_else(|_| {
+                tracing::error!("error parsing integer");
+                std::process::exit(1)
+            })
         }
     }
 }